@@ -0,0 +1,41 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use rbstar::experiment::RBExperiment;
+use rbstar::{RBRanking, RBSet};
+
+/// A synthetic experiment of 10k queries, each with a 100 element ranking and
+/// every third element relevant.
+fn synthetic_experiment() -> RBExperiment {
+    let mut experiment = RBExperiment::new(0.8).unwrap();
+    for q in 0..10_000 {
+        let ranking: Vec<Vec<String>> = (0..100)
+            .map(|d| vec![format!("d{}", (d * 7 + q) % 150)])
+            .collect();
+        let relevant = (0..150).filter(|d| d % 3 == 0).map(|d| format!("d{d}"));
+        experiment
+            .add_query(
+                format!("q{q}"),
+                RBRanking::from(ranking),
+                RBSet::from_binary(relevant, Vec::<String>::new()),
+            )
+            .unwrap();
+    }
+    experiment
+}
+
+fn evaluate(c: &mut Criterion) {
+    let experiment = synthetic_experiment();
+    let mut group = c.benchmark_group("evaluate");
+    group.bench_function("sequential", |b| {
+        b.iter(|| black_box(&experiment).evaluate())
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| black_box(&experiment).evaluate_parallel())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, evaluate);
+criterion_main!(benches);
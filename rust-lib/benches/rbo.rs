@@ -0,0 +1,44 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use rbstar::{rbo, RBRanking};
+
+/// A pair of 1000 element rankings over integer ids, the second a shuffled
+/// rotation of the first with some ids replaced.
+fn id_rankings() -> (RBRanking<u64>, RBRanking<u64>) {
+    let a = (0..1000u64).map(|d| vec![d]).collect();
+    let b = (0..1000u64)
+        .map(|d| {
+            vec![if d % 10 == 0 {
+                d + 5000
+            } else {
+                (d * 7 + 13) % 1000
+            }]
+        })
+        .collect();
+    (a, b)
+}
+
+fn to_strings(ranking: &RBRanking<u64>) -> RBRanking {
+    ranking
+        .iter()
+        .map(|group| group.iter().map(|id| id.to_string()).collect())
+        .collect()
+}
+
+fn compare(c: &mut Criterion) {
+    let (a, b) = id_rankings();
+    let (a_str, b_str) = (to_strings(&a), to_strings(&b));
+    let mut group = c.benchmark_group("rbo");
+    group.bench_function("u64", |bench| {
+        bench.iter(|| rbo(black_box(&a), black_box(&b), 0.9))
+    });
+    group.bench_function("string", |bench| {
+        bench.iter(|| rbo(black_box(&a_str), black_box(&b_str), 0.9))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, compare);
+criterion_main!(benches);
@@ -1 +1,2 @@
-from _rbpy import RBStruct, rb_function
\ No newline at end of file
+from _rbpy import RBRanking, rbo, rbo_complete, rbo_ext, rbo_matrix, RboResult, read_trec_run, read_trec_qrels, check_query_alignment, rbp, rbp_compare, RbpBounds, rbp_contributions, rbp_multi, rbp_expected_depth, rbp_phi_from_expected_depth, paired_bootstrap, jackknife_se
+from rbpy.measures import RBP, RBO
\ No newline at end of file
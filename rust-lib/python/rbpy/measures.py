@@ -0,0 +1,138 @@
+"""Measures following the ``ir_measures`` protocol, so that rbstar can be used
+as a provider alongside the rest of that ecosystem.
+
+Qrels may be given as a path to a TREC qrels file, a dict of
+``{query_id: {doc_id: relevance}}``, or an iterable of records with
+``query_id``, ``doc_id`` and ``relevance`` attributes, such as
+``ir_measures.Qrel``. Runs likewise may be a path to a TREC run file, a dict of
+``{query_id: {doc_id: score}}``, or an iterable of records with ``query_id``,
+``doc_id`` and ``score`` attributes, such as ``ir_measures.ScoredDoc``.
+Documents with equal scores form a tie group.
+
+Only queries that appear in both the qrels and the run are scored; a
+``UserWarning`` is raised if fewer than half of the run queries have qrels.
+"""
+
+import os
+import warnings
+from collections import defaultdict, namedtuple
+
+from _rbpy import RBRanking, check_query_alignment, rbo_ext, rbp, read_trec_qrels, read_trec_run
+
+try:
+    from ir_measures import Metric
+except ImportError:
+    Metric = namedtuple("Metric", ["query_id", "measure", "value"])
+
+
+def _read_qrels(qrels):
+    if isinstance(qrels, (str, os.PathLike)):
+        return read_trec_qrels(qrels)
+    if isinstance(qrels, dict):
+        return qrels
+    grouped = defaultdict(dict)
+    for qrel in qrels:
+        grouped[qrel.query_id][qrel.doc_id] = qrel.relevance
+    return grouped
+
+
+def _by_score(scores):
+    """Groups ``{doc_id: score}`` into tie groups in descending score order."""
+    groups = defaultdict(list)
+    for doc_id, score in scores.items():
+        groups[score].append(doc_id)
+    return RBRanking([groups[score] for score in sorted(groups, reverse=True)])
+
+
+def _read_run(run):
+    if isinstance(run, (str, os.PathLike)):
+        return read_trec_run(run)
+    if not isinstance(run, dict):
+        grouped = defaultdict(dict)
+        for scored in run:
+            grouped[scored.query_id][scored.doc_id] = scored.score
+        run = grouped
+    return {
+        qid: ranking if isinstance(ranking, RBRanking) else _by_score(ranking)
+        for qid, ranking in run.items()
+    }
+
+
+class _Measure:
+    """The ``ir_measures`` protocol shared by the measures below."""
+
+    def iter_calc(self, qrels, run):
+        """Yields a ``Metric(query_id, measure, value)`` for every query in
+        both the qrels and the run, in query id order."""
+        qrels = _read_qrels(qrels)
+        run = _read_run(run)
+        warning = check_query_alignment(run, qrels)
+        if warning is not None:
+            warnings.warn(warning, stacklevel=2)
+        for qid in sorted(run.keys() & qrels.keys()):
+            yield Metric(qid, self, self._score(run[qid], qrels[qid]))
+
+    def calc_aggregate(self, qrels, run):
+        """Returns the mean value over the queries of ``iter_calc``, or NaN if
+        there are none."""
+        values = [metric.value for metric in self.iter_calc(qrels, run)]
+        return sum(values) / len(values) if values else float("nan")
+
+    def _params(self):
+        raise NotImplementedError
+
+    def __repr__(self):
+        params = ",".join(f"{name}={value}" for name, value in self._params())
+        return f"{type(self).__name__}({params})"
+
+    def __eq__(self, other):
+        return type(self) is type(other) and self._params() == other._params()
+
+    def __hash__(self):
+        return hash((type(self).__name__, self._params()))
+
+
+class RBP(_Measure):
+    """Rank-biased precision with persistence ``phi``, 0.95 by default as in
+    ``rbeval`` and the Rust library; the value of a query is the lower bound
+    of ``rbp``, counting unjudged documents as non-relevant."""
+
+    def __init__(self, phi=0.95):
+        self.phi = phi
+
+    def _params(self):
+        return (("phi", self.phi),)
+
+    def _score(self, ranking, qrels):
+        return rbp(ranking, qrels, self.phi).lower
+
+
+class RBO(_Measure):
+    """Rank-biased overlap with persistence ``p``, 0.95 by default, between
+    the run and the ideal ranking of the qrels, which orders the relevant
+    documents (grade above zero) by decreasing relevance, and documents of
+    equal grade as the run does, so that any run ranking every relevant
+    document above the rest scores 1.0. The ideal ranking is complete, so the
+    value of a query is the extrapolated ``rbo_ext`` rather than the bounds of
+    ``rbo``, or 0.0 if it has no relevant documents."""
+
+    def __init__(self, p=0.95):
+        self.p = p
+
+    def _params(self):
+        return (("p", self.p),)
+
+    def _score(self, ranking, qrels):
+        relevant = {doc_id: grade for doc_id, grade in qrels.items() if grade > 0}
+        if not relevant or not len(ranking):
+            return 0.0
+        rank = {
+            doc_id: i for i, group in enumerate(ranking.groups()) for doc_id in group
+        }
+        unretrieved = len(rank)
+
+        def ideal_order(doc_id):
+            return -relevant[doc_id], rank.get(str(doc_id), unretrieved)
+
+        ideal = RBRanking([[doc_id] for doc_id in sorted(relevant, key=ideal_order)])
+        return rbo_ext(ranking, ideal, self.p)
@@ -1,17 +1,448 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::path::PathBuf;
+
+use pyo3::exceptions::{PyIOError, PyIndexError, PyUserWarning, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use rbstar::io::DuplicatePolicy;
+use rbstar::metrics::{RbpBounds, RbpConfig};
+use rbstar::{GainFn, RBRanking, RBSet, RbError, RboResult, TieBreak};
+
+/// Converts an `RbError` into the closest matching Python exception: file
+/// errors become `OSError`, and invalid input becomes `ValueError`.
+fn to_py_err(err: RbError) -> PyErr {
+    match err {
+        RbError::Io(_) => PyIOError::new_err(err.to_string()),
+        _ => PyValueError::new_err(err.to_string()),
+    }
+}
+
+/// A ranking as a sequence of tie groups, e.g. `RBRanking([[1], [2, 3], [4]])`
+/// where 2 and 3 are tied. Elements are converted to strings.
+#[pyclass(name = "RBRanking", frozen)]
+struct PyRBRanking {
+    inner: RBRanking,
+}
+
+#[pymethods]
+impl PyRBRanking {
+    #[new]
+    fn new(groups: Vec<Vec<Bound<'_, PyAny>>>) -> PyResult<Self> {
+        let groups = groups
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|elem| Ok(elem.str()?.to_string()))
+                    .collect()
+            })
+            .collect::<PyResult<Vec<Vec<String>>>>()?;
+        let inner = RBRanking::from(groups);
+        inner.validate().map_err(to_py_err)?;
+        Ok(Self { inner })
+    }
+
+    /// Creates a ranking from elements and their stated ranks, where tied
+    /// elements share a rank and the next rank skips the positions they take,
+    /// e.g. `[1, 1, 3]`.
+    #[staticmethod]
+    fn from_ranks(elements: Vec<Bound<'_, PyAny>>, ranks: Vec<usize>) -> PyResult<Self> {
+        let elements = elements
+            .iter()
+            .map(|elem| Ok(elem.str()?.to_string()))
+            .collect::<PyResult<Vec<String>>>()?;
+        let inner = RBRanking::from_ranks(elements, &ranks).map_err(to_py_err)?;
+        inner.validate().map_err(to_py_err)?;
+        Ok(Self { inner })
+    }
+
+    /// The total number of elements, across all tie groups.
+    fn total_elements(&self) -> usize {
+        self.inner.total_elements()
+    }
+
+    /// The tie groups, as a list of lists.
+    fn groups(&self) -> Vec<Vec<String>> {
+        self.inner.groups().to_vec()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn __getitem__(&self, index: isize) -> PyResult<Vec<String>> {
+        let len = self.inner.len() as isize;
+        let resolved = if index < 0 { index + len } else { index };
+        if !(0..len).contains(&resolved) {
+            return Err(PyIndexError::new_err(format!(
+                "group index {index} out of range for ranking with {len} groups"
+            )));
+        }
+        Ok(self.inner.groups()[resolved as usize].clone())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("RBRanking({:?})", self.inner.groups())
+    }
+}
+
+/// Accepts either an `RBRanking` or a list of tie groups.
+fn extract_ranking(obj: &Bound<'_, PyAny>) -> PyResult<RBRanking> {
+    if let Ok(ranking) = obj.cast::<PyRBRanking>() {
+        return Ok(ranking.get().inner.clone());
+    }
+    let list = obj.cast::<PyList>()?;
+    Ok(PyRBRanking::new(list.extract()?)?.inner)
+}
+
+/// Accepts a dict of document to relevance grade. Documents are converted to
+/// strings, as in `RBRanking`.
+fn extract_qrels(obj: &Bound<'_, PyAny>) -> PyResult<RBSet> {
+    let dict = obj.cast::<PyDict>()?;
+    let graded = dict
+        .iter()
+        .map(|(docno, grade)| Ok((docno.str()?.to_string(), grade.extract::<i32>()?)))
+        .collect::<PyResult<HashMap<String, i32>>>()?;
+    Ok(RBSet::from_graded(graded))
+}
+
+/// The bounds on the RBO between two rankings.
+#[pyclass(name = "RboResult", frozen)]
+struct PyRboResult {
+    #[pyo3(get)]
+    lower: f64,
+    #[pyo3(get)]
+    residual: f64,
+    #[pyo3(get)]
+    upper: f64,
+}
+
+#[pymethods]
+impl PyRboResult {
+    /// The midpoint of the bounds.
+    fn point(&self) -> f64 {
+        self.lower + self.residual / 2.0
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "RboResult(lower={:?}, residual={:?}, upper={:?})",
+            self.lower, self.residual, self.upper
+        )
+    }
+}
+
+impl From<RboResult> for PyRboResult {
+    fn from(result: RboResult) -> Self {
+        Self {
+            lower: result.lower,
+            residual: result.residual,
+            upper: result.upper,
+        }
+    }
+}
+
+/// The bounds on the RBP of a ranking, with the expected score under a prior
+/// for unjudged documents (the lower bound if no prior is given).
+#[pyclass(name = "RbpBounds", frozen)]
+struct PyRbpBounds {
+    inner: RbpBounds,
+    expected: f64,
+}
+
+#[pymethods]
+impl PyRbpBounds {
+    #[getter]
+    fn lower(&self) -> f64 {
+        self.inner.lower
+    }
+
+    #[getter]
+    fn upper(&self) -> f64 {
+        self.inner.upper
+    }
 
+    #[getter]
+    fn residual(&self) -> f64 {
+        self.inner.residual
+    }
+
+    #[getter]
+    fn expected(&self) -> f64 {
+        self.expected
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "RbpBounds(lower={:?}, upper={:?}, residual={:?}, expected={:?})",
+            self.inner.lower, self.inner.upper, self.inner.residual, self.expected
+        )
+    }
+}
+
+/// Computes the RBP bounds of a ranking (an `RBRanking` or list of tie
+/// groups), given qrels as a dict of document to relevance grade.
+///
+/// The keyword arguments configure the variant: `depth` evaluates only the top
+/// positions, `gain` is `"linear"` (the default, where negative grades count
+/// as zero), `"exponential"` or `"signed"` (where negative grades are
+/// penalties, so the score may fall below zero), `tie_break` is one of
+/// `"group"`, `"docno_asc"`, `"docno_desc"` or `"stable"`, and `prior` is the
+/// probability of relevance of unjudged documents for the expected score.
 #[pyfunction]
-fn rb_function() {}
+#[pyo3(signature = (ranking, qrels, phi, *, depth = None, gain = "linear", tie_break = "group", prior = None))]
+fn rbp(
+    ranking: &Bound<'_, PyAny>,
+    qrels: &Bound<'_, PyAny>,
+    phi: f64,
+    depth: Option<usize>,
+    gain: &str,
+    tie_break: &str,
+    prior: Option<f64>,
+) -> PyResult<PyRbpBounds> {
+    let gain = match gain {
+        "linear" => GainFn::Linear,
+        "exponential" => GainFn::Exponential,
+        "signed" => GainFn::Signed,
+        other => return Err(PyValueError::new_err(format!("unknown gain `{other}`"))),
+    };
+    let tie_break = match tie_break {
+        "group" => TieBreak::Group,
+        "docno_asc" => TieBreak::ByDocnoAsc,
+        "docno_desc" => TieBreak::ByDocnoDesc,
+        "stable" => TieBreak::Stable,
+        other => {
+            return Err(PyValueError::new_err(format!(
+                "unknown tie_break `{other}`"
+            )))
+        }
+    };
+    let mut config = RbpConfig::default()
+        .phi(phi)
+        .gain(gain)
+        .tie_break(tie_break);
+    if let Some(depth) = depth {
+        config = config.depth(depth);
+    }
+    if let Some(prior) = prior {
+        config = config.prior(prior);
+    }
+    let config = config.build().map_err(to_py_err)?;
+    let score = rbstar::metrics::rbp_with_config(
+        &extract_ranking(ranking)?,
+        &extract_qrels(qrels)?,
+        &config,
+    )
+    .map_err(to_py_err)?;
+    Ok(PyRbpBounds {
+        inner: score.bounds,
+        expected: score.expected,
+    })
+}
 
-#[pyclass]
-struct RBStruct {}
+/// Compares the RBP bounds of two systems, returning -1, 0 or 1 when one is
+/// known to be lower, equal or higher, or `None` when the ordering is
+/// indeterminate because the intervals overlap.
+#[pyfunction]
+fn rbp_compare(a: &PyRbpBounds, b: &PyRbpBounds) -> Option<i8> {
+    rbstar::metrics::rbp_compare(&a.inner, &b.inner).map(|ordering| match ordering {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    })
+}
+
+/// Computes the RBO bounds between two rankings, given as `RBRanking`s or
+/// lists of tie groups.
+#[pyfunction]
+fn rbo(a: &Bound<'_, PyAny>, b: &Bound<'_, PyAny>, p: f64) -> PyResult<PyRboResult> {
+    rbstar::rbo(&extract_ranking(a)?, &extract_ranking(b)?, p)
+        .map(PyRboResult::from)
+        .map_err(to_py_err)
+}
+
+/// Computes the RBO between two complete rankings of the same elements, given
+/// as `RBRanking`s or lists of tie groups. Their tails are known, so the
+/// bounds meet and the residual is zero.
+#[pyfunction]
+fn rbo_complete(a: &Bound<'_, PyAny>, b: &Bound<'_, PyAny>, p: f64) -> PyResult<PyRboResult> {
+    rbstar::rbo_complete(&extract_ranking(a)?, &extract_ranking(b)?, p)
+        .map(PyRboResult::from)
+        .map_err(to_py_err)
+}
+
+/// Computes the extrapolated RBO (RBO_EXT) between two rankings, given as
+/// `RBRanking`s or lists of tie groups. It lies within the bounds from `rbo`.
+#[pyfunction]
+fn rbo_ext(a: &Bound<'_, PyAny>, b: &Bound<'_, PyAny>, p: f64) -> PyResult<f64> {
+    rbstar::rbo_ext(&extract_ranking(a)?, &extract_ranking(b)?, p).map_err(to_py_err)
+}
+
+/// Computes the symmetric matrix of pairwise RBO point estimates between a
+/// list of rankings, as a 2D numpy array if numpy is installed, else a nested
+/// list.
+#[pyfunction]
+fn rbo_matrix<'py>(
+    py: Python<'py>,
+    rankings: Vec<Bound<'py, PyAny>>,
+    p: f64,
+) -> PyResult<Bound<'py, PyAny>> {
+    let rankings = rankings
+        .iter()
+        .map(extract_ranking)
+        .collect::<PyResult<Vec<_>>>()?;
+    let matrix = py
+        .detach(|| rbstar::rbo_matrix(&rankings, p))
+        .map_err(to_py_err)?;
+    let matrix = matrix.into_pyobject(py)?.into_any();
+    match py.import("numpy") {
+        Ok(numpy) => numpy.call_method1("array", (matrix,)),
+        Err(_) => Ok(matrix),
+    }
+}
+
+/// Reads a TREC run file into a dict of query id to `RBRanking`.
+/// Disagreements between the rank column and the scores are raised as
+/// warnings. A document repeated within a query is dropped after its first
+/// occurrence with a warning when `duplicates` is `"keep_first"`, or raises
+/// `ValueError` when it is `"error"`.
+#[pyfunction]
+#[pyo3(signature = (path, *, duplicates = "keep_first"))]
+fn read_trec_run(
+    py: Python<'_>,
+    path: PathBuf,
+    duplicates: &str,
+) -> PyResult<HashMap<String, PyRBRanking>> {
+    let duplicates = match duplicates {
+        "keep_first" => DuplicatePolicy::KeepFirst,
+        "error" => DuplicatePolicy::Error,
+        other => {
+            return Err(PyValueError::new_err(format!(
+                "unknown duplicates policy `{other}`"
+            )))
+        }
+    };
+    let (rankings, diagnostics) =
+        rbstar::io::read_trec_run_with(&path, duplicates).map_err(to_py_err)?;
+    for diagnostic in diagnostics {
+        let message = CString::new(diagnostic.to_string())?;
+        PyErr::warn(py, &py.get_type::<PyUserWarning>(), &message, 1)?;
+    }
+    Ok(rankings
+        .into_iter()
+        .map(|(qid, inner)| (qid, PyRBRanking { inner }))
+        .collect())
+}
+
+/// Reads a TREC qrels file into a dict of query id to a dict of document to
+/// relevance grade.
+#[pyfunction]
+fn read_trec_qrels(path: PathBuf) -> PyResult<HashMap<String, HashMap<String, i32>>> {
+    let qrels = rbstar::io::read_trec_qrels(&path).map_err(to_py_err)?;
+    Ok(qrels
+        .into_iter()
+        .map(|(qid, set)| {
+            let grades = set
+                .iter()
+                .map(|(doc, grade)| (doc.to_string(), grade))
+                .collect();
+            (qid, grades)
+        })
+        .collect())
+}
+
+/// Compares the query ids of a run and its qrels, both dicts keyed by query
+/// id, returning a warning message if fewer than half of the run queries have
+/// judgments, which suggests the ids are formatted differently, or `None`.
+#[pyfunction]
+fn check_query_alignment(
+    run: HashMap<String, Bound<'_, PyAny>>,
+    qrels: HashMap<String, Bound<'_, PyAny>>,
+) -> Option<String> {
+    rbstar::io::check_query_alignment(&run, &qrels).warning()
+}
+
+/// Computes the RBP weighted gain at each rank of a ranking (an `RBRanking` or
+/// list of tie groups), given qrels as a
+/// dict of document to relevance grade, as a list of `(rank, weighted_gain)`
+/// tuples.
+#[pyfunction]
+fn rbp_contributions(
+    ranking: &Bound<'_, PyAny>,
+    qrels: &Bound<'_, PyAny>,
+    phi: f64,
+) -> PyResult<Vec<(usize, f64)>> {
+    rbstar::metrics::rbp_contributions(&extract_ranking(ranking)?, &extract_qrels(qrels)?, phi)
+        .map_err(to_py_err)
+}
+
+/// Computes the RBP lower bound of one ranking against each of a list of
+/// qrels dicts, e.g. from different assessors, returning one score per dict.
+#[pyfunction]
+fn rbp_multi(
+    ranking: &Bound<'_, PyAny>,
+    qrels_sets: Vec<Bound<'_, PyAny>>,
+    phi: f64,
+) -> PyResult<Vec<f64>> {
+    let qrels_sets = qrels_sets
+        .iter()
+        .map(extract_qrels)
+        .collect::<PyResult<Vec<RBSet>>>()?;
+    rbstar::metrics::rbp_multi(&extract_ranking(ranking)?, &qrels_sets, phi).map_err(to_py_err)
+}
+
+/// Returns the expected number of documents examined under persistence `phi`.
+#[pyfunction]
+fn rbp_expected_depth(phi: f64) -> PyResult<f64> {
+    rbstar::metrics::rbp_expected_depth(phi).map_err(to_py_err)
+}
+
+/// Returns the persistence under which `depth` documents are examined in
+/// expectation.
+#[pyfunction]
+fn rbp_phi_from_expected_depth(depth: f64) -> PyResult<f64> {
+    rbstar::metrics::rbp_phi_from_expected_depth(depth).map_err(to_py_err)
+}
+
+/// Computes a two-sided p-value for the difference between paired per-query
+/// scores of two systems, by a paired bootstrap seeded with `seed`.
+#[pyfunction]
+#[pyo3(signature = (a, b, iters = 10000, seed = 0))]
+fn paired_bootstrap(a: Vec<f64>, b: Vec<f64>, iters: usize, seed: u64) -> PyResult<f64> {
+    rbstar::stats::paired_bootstrap(&a, &b, iters, seed).map_err(to_py_err)
+}
+
+/// Computes the jackknife standard error of the mean of per-query scores.
+#[pyfunction]
+fn jackknife_se(per_query: Vec<f64>) -> PyResult<f64> {
+    rbstar::stats::jackknife_se(&per_query).map_err(to_py_err)
+}
 
 /// A Python module implemented in Rust. The name of this function must match
 /// the `lib.name` setting in the `Cargo.toml`, else Python will not be able to
 /// import the module.
 #[pymodule]
 fn _rbpy(m: &Bound<'_, PyModule>) -> PyResult<()> {
-    m.add_function(wrap_pyfunction!(rb_function, m)?)?;
-    m.add_class::<RBStruct>()?;
+    m.add_class::<PyRBRanking>()?;
+    m.add_function(wrap_pyfunction!(rbo, m)?)?;
+    m.add_function(wrap_pyfunction!(rbo_complete, m)?)?;
+    m.add_function(wrap_pyfunction!(rbo_ext, m)?)?;
+    m.add_function(wrap_pyfunction!(rbo_matrix, m)?)?;
+    m.add_class::<PyRboResult>()?;
+    m.add_function(wrap_pyfunction!(read_trec_run, m)?)?;
+    m.add_function(wrap_pyfunction!(read_trec_qrels, m)?)?;
+    m.add_function(wrap_pyfunction!(check_query_alignment, m)?)?;
+    m.add_function(wrap_pyfunction!(rbp, m)?)?;
+    m.add_function(wrap_pyfunction!(rbp_compare, m)?)?;
+    m.add_class::<PyRbpBounds>()?;
+    m.add_function(wrap_pyfunction!(rbp_contributions, m)?)?;
+    m.add_function(wrap_pyfunction!(rbp_multi, m)?)?;
+    m.add_function(wrap_pyfunction!(rbp_expected_depth, m)?)?;
+    m.add_function(wrap_pyfunction!(rbp_phi_from_expected_depth, m)?)?;
+    m.add_function(wrap_pyfunction!(paired_bootstrap, m)?)?;
+    m.add_function(wrap_pyfunction!(jackknife_se, m)?)?;
     Ok(())
 }
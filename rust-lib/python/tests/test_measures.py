@@ -0,0 +1,99 @@
+import math
+
+import pytest
+
+from rbpy import RBO, RBP, rbo, rbo_complete, rbp
+
+QRELS = {
+    "1": {"a": 1, "b": 1, "c": 0},
+    "2": {"d": 1},
+}
+RUN = {
+    "1": {"a": 3.0, "b": 2.0, "c": 1.0},
+    "2": {"d": 1.0, "e": 0.5},
+    "3": {"f": 1.0},
+}
+
+
+def test_rbp_iter_calc_yields_shared_queries_in_order():
+    measure = RBP(phi=0.5)
+    metrics = list(measure.iter_calc(QRELS, RUN))
+    assert [metric.query_id for metric in metrics] == ["1", "2"]
+    assert all(metric.measure is measure for metric in metrics)
+    assert metrics[0].value == pytest.approx(0.5 * (1 + 0.5))
+    assert metrics[1].value == pytest.approx(0.5)
+
+
+def test_rbp_phi_is_applied():
+    shallow = RBP(phi=0.5).calc_aggregate(QRELS, RUN)
+    deep = RBP(phi=0.9).calc_aggregate(QRELS, RUN)
+    assert shallow != pytest.approx(deep)
+
+
+def test_rbo_iter_calc_yields_shared_queries_in_order():
+    metrics = list(RBO(p=0.9).iter_calc(QRELS, RUN))
+    assert [metric.query_id for metric in metrics] == ["1", "2"]
+    assert all(0.0 <= metric.value <= 1.0 for metric in metrics)
+
+
+def test_rbo_p_is_applied():
+    run = {"1": {"c": 3.0, "a": 2.0, "b": 1.0}}
+    assert RBO(p=0.5).calc_aggregate(QRELS, run) != pytest.approx(
+        RBO(p=0.9).calc_aggregate(QRELS, run)
+    )
+
+
+def test_rbo_of_a_perfect_run_is_one():
+    metrics = list(RBO().iter_calc(QRELS, RUN))
+    assert [metric.value for metric in metrics] == [pytest.approx(1.0)] * 2
+    # Equal grades are ideal in any order, but higher grades must come first
+    grades = {f"d{i}": 1 + i % 3 for i in range(20)}
+    run = {f"d{i}": grades[f"d{i}"] + i / 100 for i in range(20)}
+    assert RBO().calc_aggregate({"1": grades}, {"1": run}) == pytest.approx(1.0)
+    backwards = {doc_id: -score for doc_id, score in run.items()}
+    assert RBO().calc_aggregate({"1": grades}, {"1": backwards}) < 0.9
+
+
+def test_rbo_ignores_non_relevant_judgments():
+    qrels = {"1": {"a": 0, "b": 0, "c": 0}}
+    run = {"1": {"a": 3.0, "b": 2.0, "c": 1.0}}
+    assert list(RBO(p=0.9).iter_calc(qrels, run))[0].value == 0.0
+
+
+def test_empty_run_yields_nothing():
+    assert list(RBP().iter_calc(QRELS, {})) == []
+    assert list(RBO().iter_calc(QRELS, {})) == []
+    assert math.isnan(RBP().calc_aggregate(QRELS, {}))
+
+
+def test_query_with_no_documents_scores_zero():
+    run = {"1": {}}
+    assert list(RBP(phi=0.8).iter_calc(QRELS, run))[0].value == 0.0
+    assert list(RBO(p=0.9).iter_calc(QRELS, run))[0].value == 0.0
+
+
+def test_measures_compare_by_params():
+    assert RBP() == RBP(phi=0.95)
+    assert RBO() == RBO(p=0.95)
+    assert RBP(phi=0.5) == RBP(phi=0.5)
+    assert RBP(phi=0.5) != RBP(phi=0.9)
+    assert RBP(phi=0.5) != RBO(p=0.5)
+    assert repr(RBO(p=0.5)) == "RBO(p=0.5)"
+
+
+def test_qrels_with_int_doc_ids_match_ranking():
+    assert rbp([[1], [2]], {1: 1, 2: 1}, 0.5).lower == pytest.approx(0.75)
+
+
+def test_rbo_complete_closes_the_tail_of_identical_lists():
+    ranking = [["a"], ["b"], ["c"]]
+    result = rbo_complete(ranking, ranking, 0.9)
+    assert result.residual == 0.0
+    assert result.lower == pytest.approx(1.0)
+    assert rbo(ranking, ranking, 0.9).residual > 0.0
+
+
+def test_misaligned_query_ids_warn():
+    run = {"q1": {"a": 1.0}, "q2": {"d": 1.0}}
+    with pytest.warns(UserWarning, match="only 0 of 2 run queries"):
+        assert list(RBP().iter_calc(QRELS, run)) == []
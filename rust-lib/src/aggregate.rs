@@ -0,0 +1,72 @@
+use crate::error::{check_len, check_weights, RbError};
+
+/// Computes the arithmetic mean of per-query `scores`, such as the lower
+/// bounds of [`crate::metrics::rbp_batch`] over a topic set.
+///
+/// Returns an error if `scores` is empty.
+pub fn mean(scores: &[f64]) -> Result<f64, RbError> {
+    if scores.is_empty() {
+        return Err(RbError::NotEnoughSamples { needed: 1, got: 0 });
+    }
+    Ok(scores.iter().sum::<f64>() / scores.len() as f64)
+}
+
+/// Computes the mean of per-query `scores` where each query counts in
+/// proportion to its weight in `weights`, at the same position: e.g. the
+/// frequency of the query in a log. A query with a weight of zero does not
+/// count at all, and equal weights give the plain [`mean`].
+///
+/// Returns an error if `scores` and `weights` differ in length, if any weight
+/// is negative or not a number, or if no query has a positive weight.
+pub fn weighted_mean(scores: &[f64], weights: &[f64]) -> Result<f64, RbError> {
+    check_len(scores.len(), weights.len())?;
+    check_weights(weights)?;
+    let total: f64 = weights.iter().sum();
+    if total == 0.0 {
+        return Err(RbError::NotEnoughSamples { needed: 1, got: 0 });
+    }
+    let weighted: f64 = scores.iter().zip(weights).map(|(s, w)| s * w).sum();
+    Ok(weighted / total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mean_of_scores() {
+        assert!((mean(&[0.2, 0.4, 0.9]).unwrap() - 0.5).abs() < 1e-12);
+        assert_eq!(
+            mean(&[]),
+            Err(RbError::NotEnoughSamples { needed: 1, got: 0 })
+        );
+    }
+
+    #[test]
+    fn weights_scale_each_query() {
+        let scores = [0.2, 0.4, 0.9];
+        let weighted = weighted_mean(&scores, &[3.0, 1.0, 0.0]).unwrap();
+        assert!((weighted - 0.25).abs() < 1e-12);
+        let equal = weighted_mean(&scores, &[2.0, 2.0, 2.0]).unwrap();
+        assert!((equal - mean(&scores).unwrap()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rejects_invalid_weights() {
+        assert_eq!(
+            weighted_mean(&[0.2, 0.4], &[1.0]),
+            Err(RbError::LengthMismatch {
+                expected: 2,
+                got: 1
+            })
+        );
+        assert_eq!(
+            weighted_mean(&[0.2, 0.4], &[1.0, -1.0]),
+            Err(RbError::InvalidWeight {
+                index: 1,
+                weight: -1.0
+            })
+        );
+        assert!(weighted_mean(&[0.2, 0.4], &[0.0, 0.0]).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use std::fmt;
+
+/// Errors raised by the rbstar metrics and loaders.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RbError {
+    /// A ranking with no elements was given where at least one is required.
+    EmptyRanking,
+    /// A ranking is malformed, e.g. has duplicate elements or invalid ranks.
+    InvalidRanking(String),
+    /// An element appears more than once in a ranking, in the query `qid` if
+    /// known.
+    DuplicateDoc { qid: Option<String>, docno: String },
+    /// An element is judged with different grades by two sets being merged.
+    ConflictingJudgment {
+        docno: String,
+        first: i32,
+        second: i32,
+    },
+    /// A persistence parameter is outside the open interval (0, 1).
+    PhiOutOfRange(f64),
+    /// A prior probability of relevance is outside [0, 1].
+    PriorOutOfRange(f64),
+    /// A probability of relevance at a rank is outside [0, 1].
+    ProbabilityOutOfRange { index: usize, prob: f64 },
+    /// A target, such as a weight to capture, cannot be reached.
+    TargetOutOfRange(f64),
+    /// Two inputs that are zipped together have different lengths.
+    LengthMismatch { expected: usize, got: usize },
+    /// A weight is negative or not a number.
+    InvalidWeight { index: usize, weight: f64 },
+    /// Too few samples were given for a statistical estimate.
+    NotEnoughSamples { needed: usize, got: usize },
+    /// A mathematical property of a metric does not hold.
+    InvariantViolated(String),
+    /// An input file could not be read.
+    Io(String),
+    /// A line of an input file could not be parsed.
+    ParseError { line: usize, msg: String },
+}
+
+impl fmt::Display for RbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RbError::EmptyRanking => write!(f, "ranking is empty"),
+            RbError::InvalidRanking(msg) => write!(f, "invalid ranking: {msg}"),
+            RbError::DuplicateDoc {
+                qid: Some(qid),
+                docno,
+            } => write!(f, "duplicate document {docno} in query {qid}"),
+            RbError::DuplicateDoc { qid: None, docno } => {
+                write!(f, "duplicate document {docno}")
+            }
+            RbError::ConflictingJudgment {
+                docno,
+                first,
+                second,
+            } => write!(
+                f,
+                "conflicting judgments of {docno}: grades {first} and {second}"
+            ),
+            RbError::PhiOutOfRange(phi) => {
+                write!(f, "phi {phi} must be between 0 and 1 exclusive")
+            }
+            RbError::PriorOutOfRange(prior) => {
+                write!(f, "prior {prior} must be between 0 and 1 inclusive")
+            }
+            RbError::ProbabilityOutOfRange { index, prob } => write!(
+                f,
+                "probability {prob} at index {index} must be between 0 and 1 inclusive"
+            ),
+            RbError::TargetOutOfRange(target) => write!(f, "target {target} is out of range"),
+            RbError::LengthMismatch { expected, got } => {
+                write!(f, "length mismatch: expected {expected}, got {got}")
+            }
+            RbError::InvalidWeight { index, weight } => {
+                write!(f, "weight {weight} at index {index} must be non-negative")
+            }
+            RbError::NotEnoughSamples { needed, got } => {
+                write!(f, "not enough samples: needed {needed}, got {got}")
+            }
+            RbError::InvariantViolated(msg) => write!(f, "invariant violated: {msg}"),
+            RbError::Io(msg) => write!(f, "I/O error: {msg}"),
+            RbError::ParseError { line, msg } => write!(f, "parse error on line {line}: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for RbError {}
+
+/// Checks that the persistence `phi` is within (0, 1).
+pub(crate) fn check_phi(phi: f64) -> Result<(), RbError> {
+    if phi > 0.0 && phi < 1.0 {
+        Ok(())
+    } else {
+        Err(RbError::PhiOutOfRange(phi))
+    }
+}
+
+/// Checks that the probability `prior` is within [0, 1].
+pub(crate) fn check_prior(prior: f64) -> Result<(), RbError> {
+    if (0.0..=1.0).contains(&prior) {
+        Ok(())
+    } else {
+        Err(RbError::PriorOutOfRange(prior))
+    }
+}
+
+/// Checks that two inputs to be zipped together have the same length.
+pub(crate) fn check_len(expected: usize, got: usize) -> Result<(), RbError> {
+    if expected == got {
+        Ok(())
+    } else {
+        Err(RbError::LengthMismatch { expected, got })
+    }
+}
+
+/// Checks that every weight is a non-negative number.
+pub(crate) fn check_weights(weights: &[f64]) -> Result<(), RbError> {
+    match weights
+        .iter()
+        .enumerate()
+        .find(|(_, w)| w.is_nan() || **w < 0.0)
+    {
+        Some((index, &weight)) => Err(RbError::InvalidWeight { index, weight }),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,462 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::aggregate::mean;
+use crate::error::{check_phi, RbError};
+use crate::io::{check_query_alignment, AlignmentReport};
+use crate::metrics::{rbp_bounds, RbpBounds};
+use crate::ranking::RBRanking;
+use crate::set::RBSet;
+
+/// A single query of an experiment: the observed ranking and its judgments.
+#[derive(Debug, Clone)]
+struct Query {
+    qid: String,
+    ranking: RBRanking,
+    qrels: RBSet,
+}
+
+/// How an experiment scores a query whose judgments have no relevant element,
+/// where RBP is zero whatever the ranking. Tools differ in their convention,
+/// so choose the one of the baseline compared against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingQrels {
+    /// Leave the query out of the scores, and so out of the mean entirely,
+    /// listing it in [`ExperimentResult::skipped`].
+    Skip,
+    /// Score the query as zero, counting it in the mean. This is the default;
+    /// the residual is kept, as judging more elements could still raise it.
+    #[default]
+    Zero,
+    /// Score the query as NaN, bounds and all, so that the means are NaN too.
+    Nan,
+}
+
+/// The RBP bounds of every query of an experiment, summarized over the queries.
+///
+/// The residuals show whether the judgments were deep enough: a query with a
+/// large residual may merit further judging.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExperimentResult {
+    /// The mean lower bound over the queries.
+    pub mean_score: f64,
+    /// The query id and bounds of each query, in query order.
+    pub per_query: Vec<(String, RbpBounds)>,
+    pub mean_residual: f64,
+    pub max_residual: f64,
+    /// The query ids left out by [`MissingQrels::Skip`], in query order.
+    pub skipped: Vec<String>,
+}
+
+impl ExperimentResult {
+    /// Summarizes the bounds of each query. With no queries, every summary
+    /// is NaN.
+    fn new(per_query: Vec<(String, RbpBounds)>, skipped: Vec<String>) -> Self {
+        let lowers: Vec<f64> = per_query.iter().map(|(_, bounds)| bounds.lower).collect();
+        let residuals: Vec<f64> = per_query
+            .iter()
+            .map(|(_, bounds)| bounds.residual)
+            .collect();
+        let max_residual = residuals
+            .iter()
+            .copied()
+            .reduce(|a, b| if b.is_nan() || b > a { b } else { a })
+            .unwrap_or(f64::NAN);
+        Self {
+            mean_score: mean(&lowers).unwrap_or(f64::NAN),
+            per_query,
+            mean_residual: mean(&residuals).unwrap_or(f64::NAN),
+            max_residual,
+            skipped,
+        }
+    }
+
+    /// Returns the score, the lower bound, of each query in query order.
+    pub fn scores(&self) -> Vec<f64> {
+        self.per_query
+            .iter()
+            .map(|(_, bounds)| bounds.lower)
+            .collect()
+    }
+}
+
+/// An RBP experiment over a set of queries, each evaluated independently with
+/// the same persistence.
+#[derive(Debug, Clone)]
+pub struct RBExperiment {
+    phi: f64,
+    missing_qrels: MissingQrels,
+    queries: Vec<Query>,
+    alignment: Option<AlignmentReport>,
+}
+
+impl RBExperiment {
+    /// Creates an experiment with no queries, or an error if `phi` is outside
+    /// (0, 1).
+    pub fn new(phi: f64) -> Result<Self, RbError> {
+        check_phi(phi)?;
+        Ok(Self {
+            phi,
+            missing_qrels: MissingQrels::default(),
+            queries: Vec::new(),
+            alignment: None,
+        })
+    }
+
+    /// Creates an experiment from a run and qrels keyed by query id, such as
+    /// those produced by [`crate::io`]. Queries are ordered by query id; a
+    /// query in the run without judgments is evaluated against an empty set.
+    /// How the query ids line up is kept in [`Self::alignment`].
+    ///
+    /// Returns an error if `phi` is outside (0, 1), or any error of
+    /// [`Self::add_query`].
+    pub fn from_run(
+        run: HashMap<String, RBRanking>,
+        qrels: &HashMap<String, RBSet>,
+        phi: f64,
+    ) -> Result<Self, RbError> {
+        let mut experiment = Self::new(phi)?;
+        experiment.alignment = Some(check_query_alignment(&run, qrels));
+        let mut run: Vec<_> = run.into_iter().collect();
+        run.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (qid, ranking) in run {
+            let judgments = qrels.get(&qid).cloned().unwrap_or_default();
+            experiment.add_query(qid, ranking, judgments)?;
+        }
+        Ok(experiment)
+    }
+
+    /// Sets how queries without relevant judgments are scored; the default is
+    /// [`MissingQrels::Zero`].
+    pub fn with_missing_qrels(mut self, missing_qrels: MissingQrels) -> Self {
+        self.missing_qrels = missing_qrels;
+        self
+    }
+
+    /// Adds a query to the end of the experiment.
+    ///
+    /// Returns [`RbError::DuplicateDoc`] if an element appears more than once
+    /// in `ranking`, as [`crate::metrics::rbp`] would, rather than credit its
+    /// gain twice.
+    pub fn add_query(
+        &mut self,
+        qid: impl Into<String>,
+        ranking: RBRanking,
+        qrels: RBSet,
+    ) -> Result<(), RbError> {
+        let qid = qid.into();
+        ranking.check_unique_docs().map_err(|err| match err {
+            RbError::DuplicateDoc { docno, .. } => RbError::DuplicateDoc {
+                qid: Some(qid.clone()),
+                docno,
+            },
+            err => err,
+        })?;
+        self.queries.push(Query {
+            qid,
+            ranking,
+            qrels,
+        });
+        Ok(())
+    }
+
+    /// Returns the number of queries.
+    pub fn len(&self) -> usize {
+        self.queries.len()
+    }
+
+    /// Returns true if the experiment has no queries.
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+
+    /// Returns how the query ids of the run lined up with those of the qrels,
+    /// for an experiment created by [`Self::from_run`]; check its
+    /// [`AlignmentReport::warning`] before trusting the mean. Queries added
+    /// one by one are not compared, so this is `None` otherwise.
+    pub fn alignment(&self) -> Option<&AlignmentReport> {
+        self.alignment.as_ref()
+    }
+
+    /// Iterates the query ids, in evaluation order.
+    pub fn qids(&self) -> impl Iterator<Item = &str> {
+        self.queries.iter().map(|query| query.qid.as_str())
+    }
+
+    /// Computes the RBP bounds of every query, in query order, and their mean
+    /// score and residuals. With [`MissingQrels::Skip`] queries without
+    /// relevant judgments are left out, so the results no longer line up with
+    /// [`Self::qids`]; match them by the query id of each result instead, and
+    /// find those left out in [`ExperimentResult::skipped`].
+    pub fn evaluate(&self) -> ExperimentResult {
+        self.summarize(self.queries.iter().map(|query| self.score(query)).collect())
+    }
+
+    /// Computes the mean RBP score over the queries, the
+    /// [`ExperimentResult::mean_score`] of [`Self::evaluate`]. Returns an
+    /// error if no query is scored.
+    pub fn mean(&self) -> Result<f64, RbError> {
+        mean(&self.evaluate().scores())
+    }
+
+    /// Computes the RBP bounds of every query across threads, in query order.
+    ///
+    /// Each query is scored by the same sequential code as [`Self::evaluate`]
+    /// and the results are collected by position, so the scores are bitwise
+    /// identical to the sequential path. Without the `rayon` feature this is
+    /// [`Self::evaluate`].
+    pub fn evaluate_parallel(&self) -> ExperimentResult {
+        #[cfg(feature = "rayon")]
+        {
+            self.summarize(
+                self.queries
+                    .par_iter()
+                    .map(|query| self.score(query))
+                    .collect(),
+            )
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            self.evaluate()
+        }
+    }
+
+    /// Collects the scores of the queries, one per query in query order, into
+    /// a result, noting the queries that were skipped.
+    fn summarize(&self, scores: Vec<Option<(String, RbpBounds)>>) -> ExperimentResult {
+        let skipped = self
+            .queries
+            .iter()
+            .zip(&scores)
+            .filter(|(_, score)| score.is_none())
+            .map(|(query, _)| query.qid.clone())
+            .collect();
+        ExperimentResult::new(scores.into_iter().flatten().collect(), skipped)
+    }
+
+    fn score(&self, query: &Query) -> Option<(String, RbpBounds)> {
+        if query.qrels.positive().next().is_none() {
+            match self.missing_qrels {
+                MissingQrels::Skip => return None,
+                // With nothing relevant the lower bound is already zero
+                MissingQrels::Zero => {}
+                MissingQrels::Nan => {
+                    let nan = RbpBounds {
+                        lower: f64::NAN,
+                        upper: f64::NAN,
+                        residual: f64::NAN,
+                    };
+                    return Some((query.qid.clone(), nan));
+                }
+            }
+        }
+        let bounds = rbp_bounds(
+            &query.ranking,
+            &query.qrels,
+            self.phi,
+            query.ranking.total_elements(),
+        );
+        Some((query.qid.clone(), bounds))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn experiment() -> RBExperiment {
+        let run = (0..50)
+            .map(|q| {
+                let ranking: Vec<Vec<String>> = (0..20)
+                    .map(|d| vec![format!("d{}", (d * (q % 22 + 1)) % 23)])
+                    .collect();
+                (format!("q{q}"), RBRanking::from(ranking))
+            })
+            .collect();
+        let qrels = (0..50)
+            .map(|q| {
+                let relevant = (0..23)
+                    .filter(|d| (d + q) % 3 == 0)
+                    .map(|d| format!("d{d}"));
+                (
+                    format!("q{q}"),
+                    RBSet::from_binary(relevant, Vec::<String>::new()),
+                )
+            })
+            .collect();
+        RBExperiment::from_run(run, &qrels, 0.8).unwrap()
+    }
+
+    #[test]
+    fn queries_are_ordered_by_qid() {
+        let experiment = experiment();
+        let qids: Vec<&str> = experiment.qids().collect();
+        let mut sorted = qids.clone();
+        sorted.sort();
+        assert_eq!(qids, sorted);
+        assert_eq!(experiment.len(), 50);
+    }
+
+    #[test]
+    fn from_run_reports_misaligned_qids() {
+        let ranking = RBRanking::from(vec![vec!["a"]]);
+        let run = HashMap::from([
+            ("401".to_string(), ranking.clone()),
+            ("402".to_string(), ranking),
+        ]);
+        let qrels = HashMap::from([("q401".to_string(), RBSet::from_binary(["a"], ["b"]))]);
+        let misaligned = RBExperiment::from_run(run, &qrels, 0.8).unwrap();
+        let alignment = misaligned.alignment().unwrap();
+        assert_eq!(alignment.run_only, ["401", "402"]);
+        assert!(alignment.warning().is_some());
+
+        assert!(experiment().alignment().unwrap().warning().is_none());
+        assert!(RBExperiment::new(0.8).unwrap().alignment().is_none());
+    }
+
+    #[test]
+    fn rejects_duplicate_elements() {
+        let mut experiment = RBExperiment::new(0.5).unwrap();
+        let duplicated = RBRanking::from(vec![vec!["x"], vec!["x"]]);
+        let qrels = RBSet::from_binary(["x"], Vec::<String>::new());
+        assert_eq!(
+            experiment.add_query("q1", duplicated.clone(), qrels.clone()),
+            Err(RbError::DuplicateDoc {
+                qid: Some("q1".to_string()),
+                docno: "x".to_string()
+            })
+        );
+        assert!(experiment.is_empty());
+
+        let run = HashMap::from([("q1".to_string(), duplicated)]);
+        let qrels = HashMap::from([("q1".to_string(), qrels)]);
+        assert!(matches!(
+            RBExperiment::from_run(run, &qrels, 0.5),
+            Err(RbError::DuplicateDoc { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_phi_out_of_range() {
+        assert_eq!(
+            RBExperiment::new(1.0).unwrap_err(),
+            RbError::PhiOutOfRange(1.0)
+        );
+    }
+
+    fn missing_qrels_experiment(missing_qrels: MissingQrels) -> RBExperiment {
+        let mut experiment = RBExperiment::new(0.5)
+            .unwrap()
+            .with_missing_qrels(missing_qrels);
+        let ranking = RBRanking::from(vec![vec!["a"], vec!["b"]]);
+        let relevant = RBSet::from_binary(["a"], Vec::<String>::new());
+        let none = RBSet::from_binary(Vec::<String>::new(), ["a", "b"]);
+        experiment
+            .add_query("q1", ranking.clone(), relevant)
+            .unwrap();
+        experiment.add_query("q2", ranking, none).unwrap();
+        experiment
+    }
+
+    #[test]
+    fn missing_qrels_policy_controls_the_mean() {
+        let zero = missing_qrels_experiment(MissingQrels::default());
+        assert_eq!(zero.evaluate().scores(), [0.5, 0.0]);
+        assert!((zero.mean().unwrap() - 0.25).abs() < 1e-12);
+
+        let skip = missing_qrels_experiment(MissingQrels::Skip);
+        assert_eq!(skip.evaluate().scores(), [0.5]);
+        assert!((skip.mean().unwrap() - 0.5).abs() < 1e-12);
+        assert_eq!(skip.evaluate_parallel(), skip.evaluate());
+
+        let nan = missing_qrels_experiment(MissingQrels::Nan);
+        assert!(nan.evaluate().scores()[1].is_nan());
+        assert!(nan.mean().unwrap().is_nan());
+    }
+
+    #[test]
+    fn skipped_queries_are_reported_and_the_rest_keep_their_qids() {
+        let mut experiment = RBExperiment::new(0.5)
+            .unwrap()
+            .with_missing_qrels(MissingQrels::Skip);
+        let ranking = RBRanking::from(vec![vec!["a"], vec!["b"]]);
+        let none = RBSet::from_binary(Vec::<String>::new(), ["a", "b"]);
+        experiment
+            .add_query("q1", ranking.clone(), none.clone())
+            .unwrap();
+        experiment
+            .add_query("q2", ranking.clone(), RBSet::from_binary(["b"], ["a"]))
+            .unwrap();
+        experiment.add_query("q3", ranking.clone(), none).unwrap();
+        experiment
+            .add_query("q4", ranking, RBSet::from_binary(["a"], ["b"]))
+            .unwrap();
+
+        let result = experiment.evaluate();
+        let per_query: Vec<(&str, f64)> = result
+            .per_query
+            .iter()
+            .map(|(qid, bounds)| (qid.as_str(), bounds.lower))
+            .collect();
+        assert_eq!(per_query, [("q2", 0.25), ("q4", 0.5)]);
+        assert_eq!(result.skipped, ["q1", "q3"]);
+        assert_eq!(experiment.evaluate_parallel(), result);
+
+        let zero = experiment.with_missing_qrels(MissingQrels::Zero).evaluate();
+        assert_eq!(zero.per_query.len(), 4);
+        assert!(zero.skipped.is_empty());
+    }
+
+    #[test]
+    fn parallel_matches_sequential_bitwise() {
+        let experiment = experiment();
+        let sequential = experiment.evaluate();
+        let parallel = experiment.evaluate_parallel();
+        let bits = |result: &ExperimentResult| {
+            let mut bits: Vec<u64> = result
+                .per_query
+                .iter()
+                .flat_map(|(_, b)| [b.lower.to_bits(), b.residual.to_bits()])
+                .collect();
+            bits.push(result.mean_score.to_bits());
+            bits
+        };
+        assert_eq!(bits(&sequential), bits(&parallel));
+    }
+
+    #[test]
+    fn summarizes_residuals_over_queries() {
+        let mut experiment = RBExperiment::new(0.5).unwrap();
+        let qrels = RBSet::from_binary(["a"], ["b"]);
+        experiment
+            .add_query(
+                "q1",
+                RBRanking::from(vec![vec!["a"], vec!["b"]]),
+                qrels.clone(),
+            )
+            .unwrap();
+        experiment
+            .add_query("q2", RBRanking::from(vec![vec!["a"], vec!["x"]]), qrels)
+            .unwrap();
+        let result = experiment.evaluate();
+        let qids: Vec<&str> = result
+            .per_query
+            .iter()
+            .map(|(qid, _)| qid.as_str())
+            .collect();
+        assert_eq!(qids, ["q1", "q2"]);
+        // q1 leaves only the tail, 0.25; q2 also leaves x unjudged, 0.25
+        assert!((result.max_residual - 0.5).abs() < 1e-12);
+        assert!((result.mean_residual - 0.375).abs() < 1e-12);
+        assert!((result.mean_score - 0.5).abs() < 1e-12);
+        assert_eq!(result.mean_score, experiment.mean().unwrap());
+        assert!(RBExperiment::new(0.5)
+            .unwrap()
+            .evaluate()
+            .mean_score
+            .is_nan());
+    }
+}
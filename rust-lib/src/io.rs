@@ -0,0 +1,644 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Lines, Read};
+use std::path::Path;
+
+use crate::error::RbError;
+use crate::ranking::RBRanking;
+use crate::set::RBSet;
+
+/// A non-fatal problem noticed while reading an input file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The 1-based line number the problem was found on.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// What to do with a document that appears more than once in the ranking of a
+/// query, which would otherwise have its gain counted twice.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Keep the highest-ranked occurrence, drop the others, and report each
+    /// dropped occurrence in the diagnostics.
+    #[default]
+    KeepFirst,
+    /// Fail with [`RbError::DuplicateDoc`].
+    Error,
+}
+
+/// A single line of a TREC run file.
+struct ScoredDoc {
+    line: usize,
+    docno: String,
+    rank: usize,
+    score: f64,
+}
+
+/// Reads a six-column TREC run file (`qid Q0 docno rank score tag`) into a
+/// ranking per query. With the `gzip` feature, a gzipped file is decompressed
+/// on the fly.
+///
+/// Documents are ordered by descending score, and documents with equal scores
+/// are collapsed into a single tie group (in file order). The `rank` column is
+/// not used for ordering, but any disagreement between it and the score-derived
+/// order is reported in the returned diagnostics. Duplicate documents are
+/// dropped as in [`DuplicatePolicy::KeepFirst`]; see [`read_trec_run_with`]
+/// to choose otherwise.
+///
+/// This reads the whole run into memory, so the lines of a query may appear
+/// anywhere in the file; for huge runs sorted by query id, stream them with
+/// [`TrecRunReader`] instead.
+pub fn read_trec_run(
+    path: &Path,
+) -> Result<(HashMap<String, RBRanking>, Vec<Diagnostic>), RbError> {
+    read_trec_run_with(path, DuplicatePolicy::default())
+}
+
+/// Reads a run file as in [`read_trec_run`], handling documents repeated
+/// within a query by `duplicates`.
+pub fn read_trec_run_with(
+    path: &Path,
+    duplicates: DuplicatePolicy,
+) -> Result<(HashMap<String, RBRanking>, Vec<Diagnostic>), RbError> {
+    let contents = read_file(path)?;
+
+    let mut by_query: HashMap<String, Vec<ScoredDoc>> = HashMap::new();
+    for (idx, line) in contents.lines().enumerate() {
+        if let Some((qid, doc)) = parse_run_line(idx + 1, line)? {
+            by_query.entry(qid).or_default().push(doc);
+        }
+    }
+
+    let mut diagnostics = Vec::new();
+    let mut rankings = HashMap::with_capacity(by_query.len());
+    for (qid, docs) in by_query {
+        let ranking = rank_by_score(&qid, docs, duplicates, &mut diagnostics)?;
+        rankings.insert(qid, ranking);
+    }
+    diagnostics.sort_by_key(|d| d.line);
+    Ok((rankings, diagnostics))
+}
+
+/// A streaming reader over a six-column TREC run file, yielding the ranking of
+/// one query at a time so that huge runs can be evaluated without holding them
+/// in memory.
+///
+/// Unlike [`read_trec_run`], the lines of each query must be contiguous, as
+/// in a file sorted by query id; a query that reappears after another one is
+/// an error rather than silently split in two. Rankings are
+/// built as in [`read_trec_run`], and the diagnostics of the queries read so
+/// far are available from [`Self::take_diagnostics`]. Duplicate documents are
+/// handled by the [`DuplicatePolicy`], by default keeping the first. The
+/// iterator stops after the first error.
+pub struct TrecRunReader<R> {
+    lines: Lines<R>,
+    line_num: usize,
+    /// The first line of the next query, read while finishing the last one
+    pending: Option<(String, ScoredDoc)>,
+    seen: HashSet<String>,
+    diagnostics: Vec<Diagnostic>,
+    duplicates: DuplicatePolicy,
+    done: bool,
+}
+
+impl TrecRunReader<Box<dyn BufRead + Send>> {
+    /// Opens the run file at `path` for streaming. With the `gzip` feature, a
+    /// gzipped file is decompressed on the fly.
+    pub fn open(path: &Path) -> Result<Self, RbError> {
+        Ok(Self::new(open_file(path)?))
+    }
+}
+
+impl<R: BufRead> TrecRunReader<R> {
+    /// Creates a reader over any buffered source of run lines.
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            line_num: 0,
+            pending: None,
+            seen: HashSet::new(),
+            diagnostics: Vec::new(),
+            duplicates: DuplicatePolicy::default(),
+            done: false,
+        }
+    }
+
+    /// Sets how documents repeated within a query are handled.
+    pub fn with_duplicate_policy(mut self, duplicates: DuplicatePolicy) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+
+    /// Returns the diagnostics reported since the last call, in line order.
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// Reads the next non-blank line, or `None` at the end of the file.
+    fn next_doc(&mut self) -> Result<Option<(String, ScoredDoc)>, RbError> {
+        for line in self.lines.by_ref() {
+            self.line_num += 1;
+            let line = line.map_err(|e| RbError::Io(format!("line {}: {e}", self.line_num)))?;
+            if let Some(doc) = parse_run_line(self.line_num, &line)? {
+                return Ok(Some(doc));
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_query(&mut self) -> Result<Option<(String, RBRanking)>, RbError> {
+        let Some((qid, first)) = self
+            .pending
+            .take()
+            .map_or_else(|| self.next_doc(), |doc| Ok(Some(doc)))?
+        else {
+            return Ok(None);
+        };
+        if !self.seen.insert(qid.clone()) {
+            return Err(RbError::ParseError {
+                line: first.line,
+                msg: format!("query {qid} is not contiguous; the run must be sorted by query id"),
+            });
+        }
+
+        let mut docs = vec![first];
+        while let Some((next_qid, doc)) = self.next_doc()? {
+            if next_qid != qid {
+                self.pending = Some((next_qid, doc));
+                break;
+            }
+            docs.push(doc);
+        }
+
+        let mut diagnostics = Vec::new();
+        let ranking = rank_by_score(&qid, docs, self.duplicates, &mut diagnostics)?;
+        diagnostics.sort_by_key(|d| d.line);
+        self.diagnostics.append(&mut diagnostics);
+        Ok(Some((qid, ranking)))
+    }
+}
+
+impl<R: BufRead> Iterator for TrecRunReader<R> {
+    type Item = Result<(String, RBRanking), RbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.next_query().transpose();
+        self.done = !matches!(next, Some(Ok(_)));
+        next
+    }
+}
+
+/// Parses a line of a run file into its query id and document, or `None` if
+/// the line is blank.
+fn parse_run_line(line_num: usize, line: &str) -> Result<Option<(String, ScoredDoc)>, RbError> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.is_empty() {
+        return Ok(None);
+    }
+    let [qid, _, docno, rank, score, _] = fields[..] else {
+        return Err(RbError::ParseError {
+            line: line_num,
+            msg: format!("expected 6 fields, found {}", fields.len()),
+        });
+    };
+    let rank = rank.parse().map_err(|_| RbError::ParseError {
+        line: line_num,
+        msg: format!("invalid rank `{rank}`"),
+    })?;
+    let score = parse_score(score).ok_or_else(|| RbError::ParseError {
+        line: line_num,
+        msg: format!("invalid score `{score}`"),
+    })?;
+    let doc = ScoredDoc {
+        line: line_num,
+        docno: docno.to_string(),
+        rank,
+        score,
+    };
+    Ok(Some((qid.to_string(), doc)))
+}
+
+/// Parses a score, ignoring surrounding whitespace: a decimal such as `0.5`
+/// or `+0.5`, possibly in scientific notation such as `1.2e-3`, or an
+/// infinity. Returns `None` for a malformed token, e.g. `0.3.3`, and for NaN,
+/// which cannot be ranked.
+fn parse_score(token: &str) -> Option<f64> {
+    token
+        .trim()
+        .parse()
+        .ok()
+        .filter(|score: &f64| !score.is_nan())
+}
+
+/// Reads a four-column TREC qrels file (`qid 0 docno rel`) into a set of
+/// judgments per query. With the `gzip` feature, a gzipped file is
+/// decompressed on the fly.
+///
+/// Relevance grades are kept as given, so explicitly judged non-relevant
+/// documents (grade zero or negative) remain distinct from unjudged ones.
+pub fn read_trec_qrels(path: &Path) -> Result<HashMap<String, RBSet>, RbError> {
+    let contents = read_file(path)?;
+
+    let mut qrels: HashMap<String, RBSet> = HashMap::new();
+    for (idx, line) in contents.lines().enumerate() {
+        let line_num = idx + 1;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+        let [qid, _, docno, rel] = fields[..] else {
+            return Err(RbError::ParseError {
+                line: line_num,
+                msg: format!("expected 4 fields, found {}", fields.len()),
+            });
+        };
+        let rel = rel.parse().map_err(|_| RbError::ParseError {
+            line: line_num,
+            msg: format!("invalid relevance `{rel}`"),
+        })?;
+        qrels.entry(qid.to_string()).or_default().add(docno, rel);
+    }
+    Ok(qrels)
+}
+
+/// How the query ids of a run line up with those of its qrels, from
+/// [`check_query_alignment`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AlignmentReport {
+    /// The queries of the run without judgments, in query id order; these
+    /// score zero against an empty set.
+    pub run_only: Vec<String>,
+    /// The judged queries missing from the run, in query id order.
+    pub qrels_only: Vec<String>,
+    /// The number of queries in both.
+    pub shared: usize,
+}
+
+impl AlignmentReport {
+    /// Returns a warning if fewer than half of the queries of the run have
+    /// judgments, which suggests that the ids of the run and the qrels are
+    /// formatted differently rather than that a few queries went unjudged.
+    pub fn warning(&self) -> Option<String> {
+        let run = self.shared + self.run_only.len();
+        if run == 0 || 2 * self.shared >= run {
+            return None;
+        }
+        let mut message = format!("only {} of {run} run queries have judgments", self.shared);
+        if let (Some(run_qid), Some(qrels_qid)) = (self.run_only.first(), self.qrels_only.first()) {
+            message += &format!(", e.g. run query {run_qid} against qrels query {qrels_qid}");
+        }
+        Some(message)
+    }
+}
+
+/// Compares the query ids of `run` and `qrels`, such as those read by
+/// [`read_trec_run`] and [`read_trec_qrels`]. A mismatch is otherwise silent:
+/// unjudged queries simply score zero, and the mean looks plausible. Only the
+/// keys are compared, so the values may be of any type.
+pub fn check_query_alignment<R, Q>(
+    run: &HashMap<String, R>,
+    qrels: &HashMap<String, Q>,
+) -> AlignmentReport {
+    let mut run_only: Vec<String> = run
+        .keys()
+        .filter(|qid| !qrels.contains_key(*qid))
+        .cloned()
+        .collect();
+    let mut qrels_only: Vec<String> = qrels
+        .keys()
+        .filter(|qid| !run.contains_key(*qid))
+        .cloned()
+        .collect();
+    run_only.sort();
+    qrels_only.sort();
+    AlignmentReport {
+        shared: run.len() - run_only.len(),
+        run_only,
+        qrels_only,
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, RbError> {
+    let mut contents = String::new();
+    open_file(path)?
+        .read_to_string(&mut contents)
+        .map_err(|e| io_error(path, e))?;
+    Ok(contents)
+}
+
+/// Opens `path` for buffered reading, decompressing it if it is gzipped: if
+/// its name ends in `.gz` or it starts with the gzip magic number.
+#[cfg(feature = "gzip")]
+fn open_file(path: &Path) -> Result<Box<dyn BufRead + Send>, RbError> {
+    const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+    let file = File::open(path).map_err(|e| io_error(path, e))?;
+    let mut reader = BufReader::new(file);
+    let named_gz = path.extension().is_some_and(|ext| ext == "gz");
+    let magic = reader.fill_buf().map_err(|e| io_error(path, e))?;
+    if named_gz || magic.starts_with(&GZIP_MAGIC) {
+        let decoder = flate2::bufread::MultiGzDecoder::new(reader);
+        Ok(Box::new(BufReader::new(decoder)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Opens `path` for buffered reading.
+#[cfg(not(feature = "gzip"))]
+fn open_file(path: &Path) -> Result<Box<dyn BufRead + Send>, RbError> {
+    let file = File::open(path).map_err(|e| io_error(path, e))?;
+    Ok(Box::new(BufReader::new(file)))
+}
+
+fn io_error(path: &Path, err: std::io::Error) -> RbError {
+    RbError::Io(format!("{}: {err}", path.display()))
+}
+
+/// Orders a query's documents by descending score, collapsing equal scores
+/// into tie groups, and reports documents whose stated rank is above that of a
+/// higher-scoring document. Repeated documents are handled by `duplicates`.
+fn rank_by_score(
+    qid: &str,
+    mut docs: Vec<ScoredDoc>,
+    duplicates: DuplicatePolicy,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<RBRanking, RbError> {
+    docs.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    // The sort is stable, so the first occurrence is the highest-ranked one
+    let mut kept: HashMap<String, usize> = HashMap::new();
+    let mut unique = Vec::with_capacity(docs.len());
+    for doc in docs {
+        let Some(&line) = kept.get(&doc.docno) else {
+            kept.insert(doc.docno.clone(), doc.line);
+            unique.push(doc);
+            continue;
+        };
+        match duplicates {
+            DuplicatePolicy::KeepFirst => diagnostics.push(Diagnostic {
+                line: doc.line,
+                message: format!(
+                    "query {qid}: dropped duplicate document {}, keeping line {line}",
+                    doc.docno
+                ),
+            }),
+            DuplicatePolicy::Error => {
+                return Err(RbError::DuplicateDoc {
+                    qid: Some(qid.to_string()),
+                    docno: doc.docno,
+                })
+            }
+        }
+    }
+    let docs = unique;
+
+    let mut ranking = RBRanking::new();
+    let mut max_rank_above = 0;
+    for group in docs.chunk_by(|a, b| a.score == b.score) {
+        for doc in group {
+            if doc.rank < max_rank_above {
+                diagnostics.push(Diagnostic {
+                    line: doc.line,
+                    message: format!(
+                        "query {qid}: document {} has rank {} but scores below a document ranked {max_rank_above}",
+                        doc.docno, doc.rank
+                    ),
+                });
+            }
+        }
+        max_rank_above = group
+            .iter()
+            .map(|doc| doc.rank)
+            .fold(max_rank_above, usize::max);
+        ranking.append(group.iter().map(|doc| doc.docno.clone()).collect());
+    }
+    Ok(ranking)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    fn write_temp(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rbstar-{}-{name}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn groups_by_query_and_collapses_ties() {
+        let path = write_temp(
+            "ties.trec",
+            "1 Q0 a 1 3.0 run\n1 Q0 b 2 2.0 run\n1 Q0 c 3 2.0 run\n\n2 Q0 d 1 1.0 run\n",
+        );
+        let (rankings, diagnostics) = read_trec_run(&path).unwrap();
+        assert_eq!(rankings.len(), 2);
+        assert_eq!(
+            rankings["1"],
+            RBRanking::from(vec![vec!["a"], vec!["b", "c"]])
+        );
+        assert_eq!(rankings["2"], RBRanking::from(vec![vec!["d"]]));
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn orders_by_score_and_reports_rank_disagreement() {
+        let path = write_temp("ranks.trec", "1 Q0 a 1 1.0 run\n1 Q0 b 2 5.0 run\n");
+        let (rankings, diagnostics) = read_trec_run(&path).unwrap();
+        assert_eq!(rankings["1"], RBRanking::from(vec![vec!["b"], vec!["a"]]));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 1);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzipped_run_matches_its_uncompressed_twin() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let plain = read_trec_run(&fixtures.join("run.trec")).unwrap();
+        let gzipped = read_trec_run(&fixtures.join("run.trec.gz")).unwrap();
+        assert_eq!(plain, gzipped);
+        assert_eq!(plain.0.len(), 2);
+    }
+
+    #[test]
+    fn parses_scores_in_any_notation() {
+        assert_eq!(parse_score("1.2e-3"), Some(0.0012));
+        assert_eq!(parse_score("+0.5"), Some(0.5));
+        assert_eq!(parse_score("  0.33 "), Some(0.33));
+        assert_eq!(parse_score("-2E2"), Some(-200.0));
+        assert_eq!(parse_score("0.3.3"), None);
+        assert_eq!(parse_score("0,5"), None);
+        assert_eq!(parse_score("NaN"), None);
+
+        let (_, doc) = parse_run_line(1, "1 Q0 a 1 1.2e-3 run \t")
+            .unwrap()
+            .unwrap();
+        assert_eq!(doc.score, 0.0012);
+        assert_eq!(
+            parse_run_line(7, "1 Q0 a 1 0.3.3 run").err(),
+            Some(RbError::ParseError {
+                line: 7,
+                msg: "invalid score `0.3.3`".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn alignment_reports_disjoint_queries() {
+        let ranking = RBRanking::from(vec![vec!["a"]]);
+        let run: HashMap<String, RBRanking> = ["401", "402", "403", "404"]
+            .into_iter()
+            .map(|qid| (qid.to_string(), ranking.clone()))
+            .collect();
+        let qrels: HashMap<String, RBSet> = ["401", "q402", "q403", "q404", "q405"]
+            .into_iter()
+            .map(|qid| (qid.to_string(), RBSet::new()))
+            .collect();
+        let report = check_query_alignment(&run, &qrels);
+        assert_eq!(report.shared, 1);
+        assert_eq!(report.run_only, ["402", "403", "404"]);
+        assert_eq!(report.qrels_only, ["q402", "q403", "q404", "q405"]);
+        assert_eq!(
+            report.warning().unwrap(),
+            "only 1 of 4 run queries have judgments, e.g. run query 402 against qrels query q402"
+        );
+        assert_eq!(
+            check_query_alignment(&run, &HashMap::<String, RBSet>::new()).shared,
+            0
+        );
+
+        let mostly = AlignmentReport {
+            run_only: vec!["404".to_string()],
+            qrels_only: Vec::new(),
+            shared: 3,
+        };
+        assert_eq!(mostly.warning(), None);
+    }
+
+    #[test]
+    fn rejects_short_lines() {
+        let path = write_temp("short.trec", "1 Q0 a 1 1.0 run\n1 Q0 b 2\n");
+        assert_eq!(
+            read_trec_run(&path).unwrap_err(),
+            RbError::ParseError {
+                line: 2,
+                msg: "expected 6 fields, found 4".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn streams_one_query_at_a_time() {
+        let run = "1 Q0 a 1 2.0 run\n1 Q0 b 2 1.0 run\n\n2 Q0 c 1 1.0 run\n";
+        let mut reader = TrecRunReader::new(run.as_bytes());
+        let (qid, ranking) = reader.next().unwrap().unwrap();
+        assert_eq!(qid, "1");
+        assert_eq!(ranking, RBRanking::from(vec![vec!["a"], vec!["b"]]));
+        assert_eq!(reader.next().unwrap().unwrap().0, "2");
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn streaming_rejects_non_contiguous_queries() {
+        let run = "1 Q0 a 1 1.0 run\n2 Q0 b 1 1.0 run\n1 Q0 c 2 0.5 run\n2 Q0 d 2 0.5 run\n";
+        // Reading the whole run groups the lines of each query wherever they are
+        let path = write_temp("interleaved.trec", run);
+        let (rankings, diagnostics) = read_trec_run(&path).unwrap();
+        assert_eq!(rankings["1"], RBRanking::from(vec![vec!["a"], vec!["c"]]));
+        assert_eq!(rankings["2"], RBRanking::from(vec![vec!["b"], vec!["d"]]));
+        assert!(diagnostics.is_empty());
+
+        let mut reader = TrecRunReader::new(run.as_bytes());
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(
+            reader.next(),
+            Some(Err(RbError::ParseError { line: 3, .. }))
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn drops_or_rejects_duplicate_documents() {
+        let run = "1 Q0 a 1 3.0 run\n1 Q0 b 2 2.0 run\n1 Q0 a 3 1.0 run\n";
+        let mut reader = TrecRunReader::new(run.as_bytes());
+        let (_, ranking) = reader.next().unwrap().unwrap();
+        assert_eq!(ranking, RBRanking::from(vec![vec!["a"], vec!["b"]]));
+        let diagnostics = reader.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 3);
+
+        let mut reader =
+            TrecRunReader::new(run.as_bytes()).with_duplicate_policy(DuplicatePolicy::Error);
+        assert_eq!(
+            reader.next().unwrap(),
+            Err(RbError::DuplicateDoc {
+                qid: Some("1".to_string()),
+                docno: "a".to_string()
+            })
+        );
+
+        let path = write_temp("duplicates.trec", run);
+        let (rankings, diagnostics) = read_trec_run(&path).unwrap();
+        assert_eq!(rankings["1"].total_elements(), 2);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(matches!(
+            read_trec_run_with(&path, DuplicatePolicy::Error),
+            Err(RbError::DuplicateDoc { .. })
+        ));
+    }
+
+    #[test]
+    fn keeps_negative_judgments_distinct_from_unjudged() {
+        let path = write_temp("grades.qrels", "1 0 a 2\n1 0 b 0\n1 0 c -1\n2 0 a 1\n");
+        let qrels = read_trec_qrels(&path).unwrap();
+        assert_eq!(qrels.len(), 2);
+        assert_eq!(qrels["1"].grade("a"), Some(2));
+        assert_eq!(qrels["1"].grade("c"), Some(-1));
+        assert!(qrels["1"].is_negative("c"));
+        assert_eq!(qrels["1"].grade("d"), None);
+    }
+
+    #[test]
+    fn rejects_malformed_qrels_lines() {
+        let path = write_temp("bad.qrels", "1 0 a 1\n\n1 0 b 1 extra\n");
+        assert_eq!(
+            read_trec_qrels(&path).unwrap_err(),
+            RbError::ParseError {
+                line: 3,
+                msg: "expected 4 fields, found 5".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn reads_fixture_qrels() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../rbstar/tests/runs/1-9.qrels");
+        let qrels = read_trec_qrels(&path).unwrap();
+        assert_eq!(qrels.len(), 9);
+        assert_eq!(qrels["1"].total_elements(), 1512);
+    }
+
+    #[test]
+    fn reads_fixture_run() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../rbstar/tests/runs/test-one.trec");
+        let (rankings, diagnostics) = read_trec_run(&path).unwrap();
+        assert_eq!(rankings.len(), 9);
+        assert_eq!(rankings["8"].total_elements(), 707);
+        assert!(diagnostics.is_empty());
+    }
+}
@@ -1 +1,21 @@
+pub mod aggregate;
+pub mod error;
+pub mod experiment;
+pub mod io;
+pub mod metrics;
+pub mod ranking;
+pub mod rba;
+pub mod rbo;
+pub mod set;
+pub mod stats;
 
+pub use error::RbError;
+pub use experiment::{ExperimentResult, RBExperiment};
+pub use metrics::GainFn;
+pub use ranking::{RBRanking, TieBreak};
+pub use rba::rba;
+pub use rbo::{
+    check_invariants, rbo, rbo_best_match, rbo_complete, rbo_depth_for_residual, rbo_ext,
+    rbo_matrix, rbo_reversed, rbo_weighted, RboResult,
+};
+pub use set::{MergePolicy, RBSet};
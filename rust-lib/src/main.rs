@@ -0,0 +1,258 @@
+//! `rbeval`: evaluates a TREC run with RBP or RBO and prints per-query and
+//! mean scores as TSV.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use rbstar::aggregate::mean;
+use rbstar::io::{check_query_alignment, read_trec_qrels, read_trec_run};
+use rbstar::metrics::rbp_at_depth;
+use rbstar::{rbo, RBRanking, RbError};
+
+const USAGE: &str = "\
+usage: rbeval --run <run> --metric rbp --qrels <qrels> [--phi <phi>] [--depth <depth>]
+       rbeval --run <run> --metric rbo --reference <run> [--phi <phi>]
+
+Prints a TSV line of score and residual per query, followed by the mean over
+all queries, which is NaN if there are none. For rbo, each query is compared
+with its ranking in --reference. The persistence --phi defaults to 0.95.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Metric {
+    Rbp,
+    Rbo,
+}
+
+#[derive(Debug)]
+struct Args {
+    run: PathBuf,
+    qrels: Option<PathBuf>,
+    reference: Option<PathBuf>,
+    metric: Metric,
+    phi: f64,
+    depth: Option<usize>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut run = None;
+    let mut qrels = None;
+    let mut reference = None;
+    let mut metric = Metric::Rbp;
+    let mut phi = 0.95;
+    let mut depth = None;
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("{flag} requires a value"))
+        };
+        match flag.as_str() {
+            "--run" => run = Some(PathBuf::from(value()?)),
+            "--qrels" => qrels = Some(PathBuf::from(value()?)),
+            "--reference" => reference = Some(PathBuf::from(value()?)),
+            "--metric" => {
+                metric = match value()?.as_str() {
+                    "rbp" => Metric::Rbp,
+                    "rbo" => Metric::Rbo,
+                    other => return Err(format!("unknown metric `{other}`")),
+                }
+            }
+            "--phi" => {
+                let value = value()?;
+                phi = value
+                    .parse()
+                    .map_err(|_| format!("invalid phi `{value}`"))?;
+            }
+            "--depth" => {
+                let value = value()?;
+                depth = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid depth `{value}`"))?,
+                );
+            }
+            other => return Err(format!("unknown argument `{other}`")),
+        }
+    }
+
+    let run = run.ok_or("--run is required")?;
+    match metric {
+        Metric::Rbp if qrels.is_none() => return Err("--qrels is required for rbp".into()),
+        Metric::Rbo if reference.is_none() => return Err("--reference is required for rbo".into()),
+        Metric::Rbo if depth.is_some() => return Err("--depth only applies to rbp".into()),
+        _ => {}
+    }
+    Ok(Args {
+        run,
+        qrels,
+        reference,
+        metric,
+        phi,
+        depth,
+    })
+}
+
+/// Reads a run, reporting any diagnostics on stderr.
+fn read_run(path: &Path) -> Result<HashMap<String, RBRanking>, String> {
+    let (run, diagnostics) = read_trec_run(path).map_err(|e| context(path, e))?;
+    for diagnostic in diagnostics {
+        eprintln!("rbeval: warning: {}: {diagnostic}", path.display());
+    }
+    Ok(run)
+}
+
+fn context(path: &Path, err: RbError) -> String {
+    match err {
+        RbError::Io(_) => err.to_string(),
+        _ => format!("{}: {err}", path.display()),
+    }
+}
+
+/// Computes the `(score, residual)` of every query in the run, in query id
+/// order.
+fn evaluate(args: &Args) -> Result<Vec<(String, f64, f64)>, String> {
+    let run = read_run(&args.run)?;
+    let qrels = match &args.qrels {
+        Some(path) => read_trec_qrels(path).map_err(|e| context(path, e))?,
+        None => HashMap::new(),
+    };
+    if args.metric == Metric::Rbp {
+        if let Some(warning) = check_query_alignment(&run, &qrels).warning() {
+            eprintln!("rbeval: warning: {warning}");
+        }
+    }
+    let mut run: Vec<_> = run.into_iter().collect();
+    run.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut scores = Vec::with_capacity(run.len());
+    match args.metric {
+        Metric::Rbp => {
+            for (qid, ranking) in run {
+                let judgments = qrels.get(&qid).cloned().unwrap_or_default();
+                let depth = args.depth.unwrap_or(ranking.total_elements());
+                let bounds = rbp_at_depth(&ranking, &judgments, args.phi, depth)
+                    .map_err(|e| e.to_string())?;
+                scores.push((qid, bounds.lower, bounds.residual));
+            }
+        }
+        Metric::Rbo => {
+            let path = args.reference.as_ref().expect("checked by parse_args");
+            let reference = read_run(path)?;
+            for (qid, ranking) in run {
+                let Some(other) = reference.get(&qid) else {
+                    eprintln!("rbeval: warning: query {qid} is not in the reference, skipping");
+                    continue;
+                };
+                let result = rbo(&ranking, other, args.phi).map_err(|e| e.to_string())?;
+                scores.push((qid, result.lower, result.residual));
+            }
+        }
+    }
+    Ok(scores)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("rbeval: {msg}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let scores = match evaluate(&args) {
+        Ok(scores) => scores,
+        Err(msg) => {
+            eprintln!("rbeval: {msg}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if scores.is_empty() {
+        eprintln!("rbeval: warning: no queries were evaluated");
+    }
+
+    // A closed stdout, e.g. piped into `head`, is not an error
+    match write_tsv(&mut io::stdout().lock(), args.metric, &scores) {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+            eprintln!("rbeval: {e}");
+            ExitCode::FAILURE
+        }
+        _ => ExitCode::SUCCESS,
+    }
+}
+
+fn write_tsv(
+    out: &mut impl Write,
+    metric: Metric,
+    scores: &[(String, f64, f64)],
+) -> io::Result<()> {
+    let name = match metric {
+        Metric::Rbp => "rbp",
+        Metric::Rbo => "rbo",
+    };
+    writeln!(out, "qid\t{name}\tresidual")?;
+    for (qid, score, residual) in scores {
+        writeln!(out, "{qid}\t{score:.6}\t{residual:.6}")?;
+    }
+    // With no queries there is no mean, rather than a mean of zero
+    let summary = |values: Vec<f64>| mean(&values).unwrap_or(f64::NAN);
+    let score = summary(scores.iter().map(|&(_, score, _)| score).collect());
+    let residual = summary(scores.iter().map(|&(_, _, residual)| residual).collect());
+    writeln!(out, "all\t{score:.6}\t{residual:.6}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_rbp_arguments() {
+        let args = parse(&[
+            "--run", "a.trec", "--qrels", "a.qrels", "--phi", "0.9", "--depth", "10",
+        ])
+        .unwrap();
+        assert_eq!(args.metric, Metric::Rbp);
+        assert_eq!(args.phi, 0.9);
+        assert_eq!(args.depth, Some(10));
+    }
+
+    #[test]
+    fn phi_defaults_to_that_of_the_library() {
+        let args = parse(&["--run", "a.trec", "--qrels", "a.qrels"]).unwrap();
+        assert_eq!(args.phi, 0.95);
+    }
+
+    #[test]
+    fn rejects_missing_and_unknown_arguments() {
+        assert!(parse(&["--qrels", "a.qrels"]).is_err());
+        assert!(parse(&["--run", "a.trec"]).is_err());
+        assert!(parse(&["--run", "a.trec", "--metric", "ndcg"]).is_err());
+        assert!(parse(&[
+            "--run",
+            "a.trec",
+            "--metric",
+            "rbo",
+            "--reference",
+            "b.trec"
+        ])
+        .is_ok());
+        assert!(parse(&["--run", "a.trec", "--qrels", "a.qrels", "--phi"]).is_err());
+    }
+
+    #[test]
+    fn mean_of_no_queries_is_nan() {
+        let tsv = |scores: &[(String, f64, f64)]| {
+            let mut out = Vec::new();
+            write_tsv(&mut out, Metric::Rbp, scores).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(tsv(&[]), "qid\trbp\tresidual\nall\tNaN\tNaN\n");
+        let scores = [("1".to_string(), 0.5, 0.25), ("2".to_string(), 0.0, 0.75)];
+        assert!(tsv(&scores).ends_with("all\t0.250000\t0.500000\n"));
+    }
+}
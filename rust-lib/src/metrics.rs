@@ -0,0 +1,1225 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::error::{check_len, check_phi, check_prior, RbError};
+use crate::ranking::{RBRanking, TieBreak};
+use crate::set::{RBSet, POSITIVE_CUTOFF};
+
+/// Maps a relevance grade to the gain credited for it by [`rbp_graded`].
+#[derive(Default)]
+pub enum GainFn {
+    /// The grade itself, with negative grades giving no gain. Binary RBP is
+    /// `Linear` over a 0/1 [`RBSet`].
+    #[default]
+    Linear,
+    /// `2^grade - 1`, as in nDCG, with negative grades giving no gain.
+    Exponential,
+    /// The grade itself, negative grades included, so that elements known to
+    /// be harmful incur a penalty and the score can fall below zero. The
+    /// lower bound still credits unjudged elements with nothing rather than
+    /// the largest penalty.
+    Signed,
+    /// An arbitrary mapping.
+    Custom(Box<dyn Fn(i32) -> f64 + Send + Sync>),
+}
+
+impl GainFn {
+    /// Returns the gain for relevance grade `grade`.
+    pub fn gain(&self, grade: i32) -> f64 {
+        match self {
+            GainFn::Linear => grade.max(0) as f64,
+            GainFn::Exponential => 2f64.powi(grade.max(0)) - 1.0,
+            GainFn::Signed => grade as f64,
+            GainFn::Custom(f) => f(grade),
+        }
+    }
+
+    /// Returns the largest gain of any element of `qrels`, and at least the
+    /// gain of a minimally positive element, bounding unjudged elements.
+    fn max_gain(&self, qrels: &RBSet) -> f64 {
+        qrels
+            .iter()
+            .map(|(_, grade)| self.gain(grade))
+            .fold(self.gain(POSITIVE_CUTOFF), f64::max)
+    }
+}
+
+impl fmt::Debug for GainFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GainFn::Linear => write!(f, "Linear"),
+            GainFn::Exponential => write!(f, "Exponential"),
+            GainFn::Signed => write!(f, "Signed"),
+            GainFn::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// The bounds on the Rank-Biased Precision of a ranking.
+///
+/// `lower` is the conventional RBP score, treating every unjudged element as
+/// non-relevant; `upper` instead treats every unjudged element in the ranking
+/// as relevant. The `residual` additionally includes the weight of the
+/// unevaluated tail beyond the end of the ranking, so that
+/// `lower <= upper <= lower + residual`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RbpBounds {
+    pub lower: f64,
+    pub upper: f64,
+    pub residual: f64,
+}
+
+/// Compares the RBP of two systems, accounting for the residual: the true
+/// score of each lies somewhere in `[lower, lower + residual]`. Returns the
+/// ordering of `a` relative to `b` only when the intervals are disjoint, or
+/// `Equal` when both are exact and the same; when the intervals overlap the
+/// ordering is indeterminate and `None` is returned.
+pub fn rbp_compare(a: &RbpBounds, b: &RbpBounds) -> Option<Ordering> {
+    let (a_max, b_max) = (a.lower + a.residual, b.lower + b.residual);
+    if a_max < b.lower {
+        Some(Ordering::Less)
+    } else if a.lower > b_max {
+        Some(Ordering::Greater)
+    } else if a.residual == 0.0 && b.residual == 0.0 && a.lower == b.lower {
+        Some(Ordering::Equal)
+    } else {
+        None
+    }
+}
+
+/// Computes the Rank-Biased Precision of `ranking` against `qrels` with
+/// persistence `phi`. The position at rank `i` carries weight
+/// `(1-phi)phi^(i-1)`; the score is the total weight of the positions,
+/// each scaled by the gain of the element there. The gain is the element's
+/// relevance grade, so a binary [`RBSet`] gives the usual 0/1 indicator.
+/// Unjudged elements, and the tail beyond the ranking, are bounded by the
+/// largest gain in `qrels` (at least one).
+///
+/// Negative grades count as zero, as in [`GainFn::Linear`], so that qrels
+/// using them for junk or spam, as some TREC tracks do, score as they do in
+/// other tools. Penalizing such elements is opt-in: pass [`GainFn::Signed`]
+/// to [`rbp_graded`] or [`rbp_with_config`], and the score may fall below
+/// zero.
+///
+/// Ties share the credit: each tie group contributes the summed weight of the
+/// positions it occupies multiplied by the mean gain of its elements.
+/// This makes the metric invariant to any permutation within a tie group.
+///
+/// Example: with `phi=0.8`, elements `[1]` and `[2]` relevant and `[3]`
+/// non-relevant, the lower bound is `0.2 + 0.16 = 0.360`.
+///
+/// Returns an error if `phi` is outside (0, 1), or [`RbError::DuplicateDoc`]
+/// if an element appears more than once in `ranking`, as its gain would be
+/// counted twice. The other RBP variants check the ranking in the same way.
+pub fn rbp(ranking: &RBRanking, qrels: &RBSet, phi: f64) -> Result<RbpBounds, RbError> {
+    check_phi(phi)?;
+    ranking.check_unique_docs()?;
+    Ok(rbp_bounds(ranking, qrels, phi, ranking.total_elements()))
+}
+
+/// Computes an expected RBP of `ranking`, where each unjudged element is
+/// credited with an estimated probability of relevance `prior` in place of
+/// the lower and upper bounds of [`rbp`]; judged elements contribute their
+/// gain as usual. Only the elements of the ranking are scored, so the tail
+/// beyond its end contributes nothing. With `prior = 0` this is the lower
+/// bound of [`rbp`].
+///
+/// Returns an error if `phi` is outside (0, 1) or `prior` is outside [0, 1].
+pub fn rbp_with_prior(
+    ranking: &RBRanking,
+    qrels: &RBSet,
+    phi: f64,
+    prior: f64,
+) -> Result<f64, RbError> {
+    check_phi(phi)?;
+    check_prior(prior)?;
+    ranking.check_unique_docs()?;
+    let depth = ranking.total_elements();
+    Ok(weighted_gain(ranking, phi, depth, |e| {
+        qrels.grade(e).map_or(prior, |_| qrels.gain(e))
+    }))
+}
+
+/// Computes the expected RBP of a ranking given only the probability of
+/// relevance at each rank, `(1 - phi) * sum(probs[i] * phi^i)`, with no
+/// element names or judgments involved: e.g. to simulate rankings, or to plot
+/// RBP against `phi`. With probabilities of zero and one this is the lower
+/// bound of [`rbp`] for a ranking without ties.
+///
+/// Returns an error if `phi` is outside (0, 1) or any probability is outside
+/// [0, 1].
+pub fn rbp_from_probs(probs: &[f64], phi: f64) -> Result<f64, RbError> {
+    check_phi(phi)?;
+    let mut score = 0.0;
+    let mut weight = 1.0 - phi;
+    for (index, &prob) in probs.iter().enumerate() {
+        if !(0.0..=1.0).contains(&prob) {
+            return Err(RbError::ProbabilityOutOfRange { index, prob });
+        }
+        score += weight * prob;
+        weight *= phi;
+    }
+    Ok(score)
+}
+
+/// Computes [`rbp`] over only the top `depth` positions of `ranking`; the
+/// weight of every position beyond `depth` is folded into the residual, as
+/// `phi^depth` scaled by the largest gain. A tie group that crosses the depth
+/// boundary contributes its mean gain for just the positions it occupies
+/// within the top `depth`. With `depth` at least the length of the ranking
+/// this is the same as [`rbp`].
+pub fn rbp_at_depth(
+    ranking: &RBRanking,
+    qrels: &RBSet,
+    phi: f64,
+    depth: usize,
+) -> Result<RbpBounds, RbError> {
+    check_phi(phi)?;
+    ranking.check_unique_docs()?;
+    Ok(rbp_bounds(ranking, qrels, phi, depth))
+}
+
+/// Computes [`rbp`] with the gain of each judged element given by `gain`
+/// applied to its grade, e.g. [`GainFn::Exponential`] to weight highly
+/// relevant elements as in nDCG. Unjudged elements and the tail are bounded
+/// by the largest gain in `qrels`, and at least the gain of a grade of one.
+/// [`rbp`] is the special case of [`GainFn::Linear`].
+pub fn rbp_graded(
+    ranking: &RBRanking,
+    qrels: &RBSet,
+    phi: f64,
+    gain: &GainFn,
+) -> Result<RbpBounds, RbError> {
+    check_phi(phi)?;
+    ranking.check_unique_docs()?;
+    Ok(graded_bounds(
+        ranking,
+        qrels,
+        phi,
+        ranking.total_elements(),
+        gain,
+    ))
+}
+
+/// The configuration of [`rbp_with_config`], gathering every option of the RBP
+/// variants in one place. Start from the default and override as needed:
+///
+/// ```
+/// use rbstar::metrics::RbpConfig;
+/// use rbstar::GainFn;
+///
+/// let config = RbpConfig::default()
+///     .phi(0.8)
+///     .depth(10)
+///     .gain(GainFn::Exponential)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct RbpConfig {
+    phi: f64,
+    depth: Option<usize>,
+    gain: GainFn,
+    tie_break: TieBreak,
+    prior: Option<f64>,
+}
+
+impl Default for RbpConfig {
+    /// A persistence of 0.95, as in the Python reference implementation,
+    /// evaluated over the whole ranking with linear gain, shared credit within
+    /// tie groups, and no prior.
+    fn default() -> Self {
+        Self {
+            phi: 0.95,
+            depth: None,
+            gain: GainFn::Linear,
+            tie_break: TieBreak::Group,
+            prior: None,
+        }
+    }
+}
+
+impl RbpConfig {
+    /// Sets the persistence.
+    pub fn phi(mut self, phi: f64) -> Self {
+        self.phi = phi;
+        self
+    }
+
+    /// Evaluates only the top `depth` positions, as in [`rbp_at_depth`].
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Sets the mapping from grades to gains, as in [`rbp_graded`].
+    pub fn gain(mut self, gain: GainFn) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Sets how tie groups in the ranking are treated: shared credit with
+    /// [`TieBreak::Group`], or broken into singletons otherwise.
+    pub fn tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.tie_break = tie_break;
+        self
+    }
+
+    /// Sets the probability of relevance of unjudged elements used for the
+    /// expected score, as in [`rbp_with_prior`].
+    pub fn prior(mut self, prior: f64) -> Self {
+        self.prior = Some(prior);
+        self
+    }
+
+    /// Validates the configuration, returning an error if `phi` is outside
+    /// (0, 1) or the prior is outside [0, 1].
+    pub fn build(self) -> Result<Self, RbError> {
+        check_phi(self.phi)?;
+        self.prior.map_or(Ok(()), check_prior)?;
+        Ok(self)
+    }
+}
+
+/// The result of [`rbp_with_config`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RbpScore {
+    pub bounds: RbpBounds,
+    /// The expected score under the configured prior, or the lower bound if
+    /// there is none.
+    pub expected: f64,
+}
+
+/// Computes RBP of `ranking` against `qrels` with every option taken from
+/// `config`, which is validated again here. With the default options apart
+/// from `phi` this is [`rbp`].
+pub fn rbp_with_config(
+    ranking: &RBRanking,
+    qrels: &RBSet,
+    config: &RbpConfig,
+) -> Result<RbpScore, RbError> {
+    check_phi(config.phi)?;
+    ranking.check_unique_docs()?;
+    let ranking = ranking.break_ties(config.tie_break);
+    let depth = config.depth.unwrap_or(ranking.total_elements());
+    let bounds = graded_bounds(&ranking, qrels, config.phi, depth, &config.gain);
+    let expected = match config.prior {
+        None => bounds.lower,
+        Some(prior) => {
+            check_prior(prior)?;
+            let unjudged = prior * config.gain.gain(POSITIVE_CUTOFF);
+            let depth = depth.min(ranking.total_elements());
+            weighted_gain(&ranking, config.phi, depth, |e| {
+                qrels
+                    .grade(e)
+                    .map_or(unjudged, |grade| config.gain.gain(grade))
+            })
+        }
+    };
+    Ok(RbpScore { bounds, expected })
+}
+
+/// Computes [`rbp_at_depth`] with an already validated `phi`.
+pub(crate) fn rbp_bounds(ranking: &RBRanking, qrels: &RBSet, phi: f64, depth: usize) -> RbpBounds {
+    graded_bounds(ranking, qrels, phi, depth, &GainFn::Linear)
+}
+
+fn graded_bounds(
+    ranking: &RBRanking,
+    qrels: &RBSet,
+    phi: f64,
+    depth: usize,
+    gain: &GainFn,
+) -> RbpBounds {
+    let depth = depth.min(ranking.total_elements());
+    let max_gain = gain.max_gain(qrels);
+    let lower = weighted_gain(ranking, phi, depth, |e| {
+        qrels.grade(e).map_or(0.0, |grade| gain.gain(grade))
+    });
+    let unjudged = weighted_gain(ranking, phi, depth, |e| {
+        if qrels.grade(e).is_none() {
+            max_gain
+        } else {
+            0.0
+        }
+    });
+    let tail = max_gain * phi.powi(depth as i32);
+    RbpBounds {
+        lower,
+        upper: lower + unjudged,
+        residual: unjudged + tail,
+    }
+}
+
+/// Computes the RBP of `ranking` normalized by that of the ideal ranking,
+/// which places every element of `qrels` with a positive gain at the top in
+/// decreasing order of gain. The score is the lower bound of [`rbp`] divided
+/// by the ideal score, clamped to [0, 1], so that scores are comparable across
+/// queries with different numbers of relevant elements.
+///
+/// If `qrels` has no element with a positive gain the ideal score is zero and
+/// the normalized score is undefined, so `None` is returned.
+///
+/// Normalization is only meaningful for non-negative gains, as a penalized
+/// score has no ideal to be a fraction of: the gains are always those of
+/// [`GainFn::Linear`], so negative grades count as zero rather than as the
+/// penalties of [`GainFn::Signed`].
+pub fn nrbp(ranking: &RBRanking, qrels: &RBSet, phi: f64) -> Result<Option<f64>, RbError> {
+    check_phi(phi)?;
+    ranking.check_unique_docs()?;
+    let mut gains: Vec<f64> = qrels
+        .iter()
+        .map(|(elem, _)| qrels.gain(elem))
+        .filter(|&gain| gain > 0.0)
+        .collect();
+    gains.sort_by(|a, b| b.total_cmp(a));
+
+    let mut weight = 1.0 - phi;
+    let mut ideal = 0.0;
+    for gain in gains {
+        ideal += weight * gain;
+        weight *= phi;
+    }
+    if ideal == 0.0 {
+        return Ok(None);
+    }
+    let observed = rbp_bounds(ranking, qrels, phi, ranking.total_elements()).lower;
+    Ok(Some((observed / ideal).clamp(0.0, 1.0)))
+}
+
+/// Accumulates RBP incrementally as the elements of a ranking arrive one rank
+/// at a time, so that the running score can be read at any point without
+/// recomputing it from scratch.
+///
+/// Each element is pushed with its gain, or as unjudged. The bounds returned
+/// by [`Self::score`] are those of [`rbp`] over the elements pushed so far,
+/// for a ranking without ties, with unjudged elements and the tail bounded by
+/// the maximum gain (one by default).
+#[derive(Debug, Clone)]
+pub struct RbpAccumulator {
+    phi: f64,
+    max_gain: f64,
+    rank: usize,
+    weight: f64,
+    lower: f64,
+    unjudged: f64,
+}
+
+impl RbpAccumulator {
+    /// Creates an accumulator with no elements, or an error if `phi` is
+    /// outside (0, 1).
+    pub fn new(phi: f64) -> Result<Self, RbError> {
+        check_phi(phi)?;
+        Ok(Self {
+            phi,
+            max_gain: 1.0,
+            rank: 0,
+            weight: 1.0 - phi,
+            lower: 0.0,
+            unjudged: 0.0,
+        })
+    }
+
+    /// Sets the gain that bounds unjudged elements and the tail, e.g. the
+    /// largest gain in the qrels.
+    pub fn with_max_gain(mut self, max_gain: f64) -> Self {
+        self.max_gain = max_gain;
+        self
+    }
+
+    /// Adds a judged element with `gain` at the next rank.
+    pub fn push(&mut self, gain: f64) {
+        self.lower += self.weight * gain;
+        self.advance();
+    }
+
+    /// Adds an unjudged element at the next rank.
+    pub fn push_unjudged(&mut self) {
+        self.unjudged += self.weight * self.max_gain;
+        self.advance();
+    }
+
+    /// Returns the number of elements pushed so far.
+    pub fn rank(&self) -> usize {
+        self.rank
+    }
+
+    /// Returns the RBP bounds of the elements pushed so far.
+    pub fn score(&self) -> RbpBounds {
+        let tail = self.max_gain * self.phi.powi(self.rank as i32);
+        RbpBounds {
+            lower: self.lower,
+            upper: self.lower + self.unjudged,
+            residual: self.unjudged + tail,
+        }
+    }
+
+    fn advance(&mut self) {
+        self.rank += 1;
+        self.weight *= self.phi;
+    }
+}
+
+/// Returns the fraction of the RBP weight within the top `k` positions, the
+/// probability that the user model stops within them: `1 - phi^k`. This does
+/// not depend on any ranking, and so shows how much of the metric a depth of
+/// evaluation or pooling covers.
+pub fn rbp_weight_at_depth(phi: f64, k: usize) -> Result<f64, RbError> {
+    check_phi(phi)?;
+    // A float exponent, as k may not fit in the i32 of powi
+    Ok(1.0 - phi.powf(k as f64))
+}
+
+/// Returns the smallest depth `k` at which [`rbp_weight_at_depth`] is at least
+/// `target`, e.g. the pooling depth needed to cover 90% of the weight.
+///
+/// Returns an error if `phi` is outside (0, 1), or if `target` is outside
+/// [0, 1), since no finite depth captures all of the weight.
+pub fn rbp_depth_for_weight(phi: f64, target: f64) -> Result<usize, RbError> {
+    check_phi(phi)?;
+    if !(0.0..1.0).contains(&target) {
+        return Err(RbError::TargetOutOfRange(target));
+    }
+    depth_for_power(phi, 1.0 - target)
+}
+
+/// Returns the smallest `k` for which `base^k <= target`, given `base` in
+/// (0, 1): the depth beyond which a geometric tail weighs at most `target`.
+///
+/// Returns an error if `target` is not positive; a `target` of at least one
+/// needs no depth at all.
+pub(crate) fn depth_for_power(base: f64, target: f64) -> Result<usize, RbError> {
+    if target.is_nan() || target <= 0.0 {
+        return Err(RbError::TargetOutOfRange(target));
+    }
+    if target >= 1.0 {
+        return Ok(0);
+    }
+    // Solve from the logarithms, then correct for their rounding. Near
+    // base = 1 the depth can exceed i32::MAX, so exponents are floats
+    let mut k = (target.ln() / base.ln()).ceil() as usize;
+    while k > 0 && base.powf((k - 1) as f64) <= target {
+        k -= 1;
+    }
+    while base.powf(k as f64) > target {
+        k += 1;
+    }
+    Ok(k)
+}
+
+/// Returns the expected number of elements examined by the user model of RBP
+/// with persistence `phi`, `1 / (1 - phi)`: e.g. five for `phi = 0.8`. This
+/// is a more intuitive way to communicate the choice of `phi`.
+///
+/// Returns an error if `phi` is outside (0, 1).
+pub fn rbp_expected_depth(phi: f64) -> Result<f64, RbError> {
+    check_phi(phi)?;
+    Ok(1.0 / (1.0 - phi))
+}
+
+/// Returns the persistence whose user model examines `depth` elements in
+/// expectation, `1 - 1 / depth`; the inverse of [`rbp_expected_depth`].
+///
+/// Returns an error if `depth` is not greater than one, as the user always
+/// examines the first element, or is infinite.
+pub fn rbp_phi_from_expected_depth(depth: f64) -> Result<f64, RbError> {
+    if depth.is_nan() || depth <= 1.0 || depth.is_infinite() {
+        return Err(RbError::TargetOutOfRange(depth));
+    }
+    Ok(1.0 - 1.0 / depth)
+}
+
+/// Breaks the RBP of `ranking` down into the weighted gain contributed at each
+/// rank, returning `(rank, weighted_gain)` pairs for every position. Positions
+/// in a tie group each contribute their own weight times the mean gain of the
+/// group, so the contributions sum to the lower bound of [`rbp`]. As there,
+/// negative grades contribute nothing.
+pub fn rbp_contributions(
+    ranking: &RBRanking,
+    qrels: &RBSet,
+    phi: f64,
+) -> Result<Vec<(usize, f64)>, RbError> {
+    check_phi(phi)?;
+    ranking.check_unique_docs()?;
+    let mut contributions = Vec::with_capacity(ranking.total_elements());
+    let mut weight = 1.0 - phi;
+    for group in ranking.iter().filter(|group| !group.is_empty()) {
+        let gain = group.iter().map(|e| qrels.gain(e)).sum::<f64>() / group.len() as f64;
+        for _ in group {
+            contributions.push((contributions.len() + 1, weight * gain));
+            weight *= phi;
+        }
+    }
+    Ok(contributions)
+}
+
+/// Accumulates the tie-averaged weights of the top `depth` positions of
+/// `ranking`, scaling each group by the mean of `gain` over its elements.
+fn weighted_gain(ranking: &RBRanking, phi: f64, depth: usize, gain: impl Fn(&str) -> f64) -> f64 {
+    group_weights(ranking, phi, depth)
+        .map(|(group, weight)| {
+            let total: f64 = group.iter().map(|e| gain(e)).sum();
+            weight * total / group.len() as f64
+        })
+        .sum()
+}
+
+/// The Rank-Biased Recall of a ranking, with the residual due to relevant
+/// elements that were not retrieved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RbrResult {
+    pub score: f64,
+    /// The score that would be gained if every unretrieved relevant element
+    /// were appended directly after the end of the ranking.
+    pub residual: f64,
+}
+
+/// Computes the Rank-Biased Recall of `ranking` against `qrels` with
+/// persistence `phi`: the RBP weight of the relevant retrieved elements,
+/// normalized by the weight of an ideal ranking that places all `R` relevant
+/// elements at the top, `1 - phi^R`. Ties share the credit as in [`rbp`].
+///
+/// Together with [`rbp`] this gives a top-weighted analogue of the
+/// precision/recall pair. If `qrels` has no relevant elements both the score
+/// and the residual are zero.
+///
+/// Returns an error if `phi` is outside (0, 1), or [`RbError::DuplicateDoc`]
+/// if an element appears more than once in `ranking`.
+pub fn rbr(ranking: &RBRanking, qrels: &RBSet, phi: f64) -> Result<RbrResult, RbError> {
+    check_phi(phi)?;
+    ranking.check_unique_docs()?;
+    let relevant = qrels.positive().count();
+    if relevant == 0 {
+        return Ok(RbrResult {
+            score: 0.0,
+            residual: 0.0,
+        });
+    }
+    let ideal = 1.0 - phi.powi(relevant as i32);
+
+    let retrieved = ranking.elements().filter(|e| qrels.is_positive(e)).count();
+    let missing = relevant.saturating_sub(retrieved);
+    let tail = phi.powi(ranking.total_elements() as i32) * (1.0 - phi.powi(missing as i32));
+
+    // Recall counts relevance as binary, whatever the grade
+    let score = weighted_gain(ranking, phi, ranking.total_elements(), |e| {
+        if qrels.is_positive(e) {
+            1.0
+        } else {
+            0.0
+        }
+    });
+    Ok(RbrResult {
+        score: score / ideal,
+        residual: tail / ideal,
+    })
+}
+
+/// Iterates the tie groups of `ranking` alongside the total weight of the
+/// positions each group occupies within the top `depth`.
+fn group_weights(
+    ranking: &RBRanking,
+    phi: f64,
+    depth: usize,
+) -> impl Iterator<Item = (&[String], f64)> {
+    let mut weight = 1.0 - phi;
+    let mut position = 0;
+    ranking
+        .iter()
+        .filter(|group| !group.is_empty())
+        .map_while(move |group| {
+            if position >= depth {
+                return None;
+            }
+            let mut group_weight = 0.0;
+            for _ in group.iter().take(depth - position) {
+                group_weight += weight;
+                weight *= phi;
+            }
+            position += group.len();
+            Some((group.as_slice(), group_weight))
+        })
+}
+
+/// Computes RBP for each query, using the persistence in `phis` at the same
+/// position as each ranking. This allows user patience to differ by query.
+/// The per-query scores are returned in input order for the caller to
+/// aggregate.
+pub fn rbp_per_query(
+    rankings: &[RBRanking],
+    qrels: &[RBSet],
+    phis: &[f64],
+) -> Result<Vec<RbpBounds>, RbError> {
+    check_len(rankings.len(), qrels.len())?;
+    check_len(rankings.len(), phis.len())?;
+    rankings
+        .iter()
+        .zip(qrels)
+        .zip(phis)
+        .map(|((ranking, qrels), &phi)| rbp(ranking, qrels, phi))
+        .collect()
+}
+
+/// Computes RBP for each query with a single persistence `phi`, broadcast
+/// across all queries.
+pub fn rbp_batch(
+    rankings: &[RBRanking],
+    qrels: &[RBSet],
+    phi: f64,
+) -> Result<Vec<RbpBounds>, RbError> {
+    rbp_per_query(rankings, qrels, &vec![phi; rankings.len()])
+}
+
+/// Computes the lower bound of [`rbp`] of a single `ranking` against each of
+/// several judgment sets, e.g. from different assessors, returning one score
+/// per set in input order. The weights of the tie groups are computed once
+/// and shared across the sets, and each score is identical to that of [`rbp`].
+pub fn rbp_multi(ranking: &RBRanking, qrels_sets: &[RBSet], phi: f64) -> Result<Vec<f64>, RbError> {
+    check_phi(phi)?;
+    ranking.check_unique_docs()?;
+    let weights: Vec<_> = group_weights(ranking, phi, ranking.total_elements()).collect();
+    Ok(qrels_sets
+        .iter()
+        .map(|qrels| {
+            weights
+                .iter()
+                .map(|(group, weight)| {
+                    let total: f64 = group.iter().map(|e| qrels.gain(e)).sum();
+                    weight * total / group.len() as f64
+                })
+                .sum()
+        })
+        .collect())
+}
+
+/// How [`rbp_fused`] merges several rankings into one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FuseStrategy {
+    /// Interleave the tie groups of the rankings in turn: the first group of
+    /// each ranking, then the second of each, and so on.
+    RoundRobin,
+    /// Order the elements by the sum of the RBP weights given to them by each
+    /// ranking (CombSUM), collapsing equal sums into tie groups.
+    ByScore,
+}
+
+/// Computes [`rbp`] of the fusion of `rankings` built according to `fuse`,
+/// returning the bounds along with the fused ranking. An element in several
+/// rankings is kept only at its best position in the fusion, so that it is
+/// credited once. With no rankings the fusion is empty.
+///
+/// Returns an error if `phi` is outside (0, 1).
+pub fn rbp_fused(
+    rankings: &[RBRanking],
+    qrels: &RBSet,
+    phi: f64,
+    fuse: FuseStrategy,
+) -> Result<(RbpBounds, RBRanking), RbError> {
+    check_phi(phi)?;
+    let fused = match fuse {
+        FuseStrategy::RoundRobin => round_robin(rankings),
+        FuseStrategy::ByScore => comb_sum(rankings, phi),
+    };
+    Ok((
+        rbp_bounds(&fused, qrels, phi, fused.total_elements()),
+        fused,
+    ))
+}
+
+fn round_robin(rankings: &[RBRanking]) -> RBRanking {
+    let depth = rankings.iter().map(RBRanking::len).max().unwrap_or(0);
+    let mut seen = HashSet::new();
+    let mut fused = RBRanking::new();
+    for d in 0..depth {
+        for ranking in rankings.iter().filter(|ranking| d < ranking.len()) {
+            let group: Vec<String> = ranking.groups()[d]
+                .iter()
+                .filter(|e| seen.insert(e.as_str()))
+                .cloned()
+                .collect();
+            if !group.is_empty() {
+                fused.append(group);
+            }
+        }
+    }
+    fused
+}
+
+fn comb_sum(rankings: &[RBRanking], phi: f64) -> RBRanking {
+    // Keep the elements in order of first appearance, so that ties are stable
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    let mut scores: Vec<(&str, f64)> = Vec::new();
+    for ranking in rankings {
+        for (group, weight) in group_weights(ranking, phi, ranking.total_elements()) {
+            for e in group {
+                let i = *index.entry(e.as_str()).or_insert_with(|| {
+                    scores.push((e.as_str(), 0.0));
+                    scores.len() - 1
+                });
+                scores[i].1 += weight / group.len() as f64;
+            }
+        }
+    }
+    RBRanking::from_scores(scores, TieBreak::Group)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranking(items: &[&str]) -> RBRanking {
+        items
+            .iter()
+            .map(|item| vec![*item])
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    fn qrels(positive: &[&str], negative: &[&str]) -> RBSet {
+        RBSet::from_binary(positive.iter().copied(), negative.iter().copied())
+    }
+
+    #[test]
+    fn rbp_matches_hand_computation() {
+        let r = ranking(&["1", "2", "3", "4"]);
+        let q = qrels(&["1", "2"], &["3"]);
+        let bounds = rbp(&r, &q, 0.8).unwrap();
+        assert!((bounds.lower - 0.36).abs() < 1e-12);
+        assert!((bounds.upper - 0.36 - 0.2 * 0.8f64.powi(3)).abs() < 1e-12);
+        assert!((bounds.residual - 0.8f64.powi(3)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rbp_bounds_are_consistent() {
+        let r = ranking(&["1", "2", "3", "4", "5"]);
+        let q = qrels(&["1", "4"], &["2"]);
+        let bounds = rbp(&r, &q, 0.8).unwrap();
+        assert!((bounds.lower - (0.2 + 0.2 * 0.8f64.powi(3))).abs() < 1e-12);
+        assert!(bounds.lower <= bounds.upper);
+        assert!(bounds.upper <= bounds.lower + bounds.residual);
+        // Everything other than the known non-relevant element is unknown
+        assert!((bounds.lower + bounds.residual - (1.0 - 0.16)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rbp_weights_by_grade() {
+        let r = ranking(&["1", "2", "3"]);
+        let q = RBSet::from_graded(
+            [("1", 2), ("2", 0), ("3", 1)]
+                .into_iter()
+                .map(|(elem, grade)| (elem.to_string(), grade))
+                .collect(),
+        );
+        assert!((rbp(&r, &q, 0.8).unwrap().lower - (2.0 * 0.2 + 0.128)).abs() < 1e-12);
+    }
+
+    fn graded(grades: &[(&str, i32)]) -> RBSet {
+        RBSet::from_graded(
+            grades
+                .iter()
+                .map(|&(elem, grade)| (elem.to_string(), grade))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn exponential_gain_weights_grade_three_sevenfold() {
+        let q = graded(&[("1", 3), ("2", 1)]);
+        let high = rbp_graded(&ranking(&["1"]), &q, 0.8, &GainFn::Exponential).unwrap();
+        let low = rbp_graded(&ranking(&["2"]), &q, 0.8, &GainFn::Exponential).unwrap();
+        assert!((high.lower - 7.0 * low.lower).abs() < 1e-12);
+        // The tail is bounded by the largest gain, 2^3 - 1
+        assert!((high.residual - 7.0 * 0.8).abs() < 1e-12);
+    }
+
+    #[test]
+    fn linear_gain_is_rbp() {
+        let r = ranking(&["1", "2", "3", "4"]);
+        let q = graded(&[("1", 2), ("2", 0), ("3", -1)]);
+        assert_eq!(
+            rbp_graded(&r, &q, 0.8, &GainFn::default()),
+            rbp(&r, &q, 0.8)
+        );
+        let q = graded(&[("1", 2), ("3", 1)]);
+        let halved = GainFn::Custom(Box::new(|grade| grade as f64 / 2.0));
+        let bounds = rbp_graded(&r, &q, 0.8, &halved).unwrap();
+        assert!((bounds.lower - (0.2 + 0.128 * 0.5)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rbp_is_invariant_within_tie_groups() {
+        let q = qrels(&["1", "3"], &[]);
+        let orderings = [["1", "2", "3"], ["2", "3", "1"], ["3", "1", "2"]];
+        let scores: Vec<f64> = orderings
+            .iter()
+            .map(|first| {
+                let r = RBRanking::from(vec![first.to_vec(), vec!["4"]]);
+                rbp(&r, &q, 0.8).unwrap().lower
+            })
+            .collect();
+        // Two thirds of the weight of ranks 1 to 3
+        assert!((scores[0] - 2.0 / 3.0 * 0.488).abs() < 1e-12);
+        assert!(scores.iter().all(|&score| score == scores[0]));
+    }
+
+    #[test]
+    fn prior_credits_unjudged_elements() {
+        let r = ranking(&["1", "2", "3", "4"]);
+        let q = qrels(&["1", "2"], &["3"]);
+        let lower = rbp(&r, &q, 0.8).unwrap().lower;
+        assert_eq!(rbp_with_prior(&r, &q, 0.8, 0.0).unwrap(), lower);
+        let expected = lower + 0.1 * 0.2 * 0.8f64.powi(3);
+        assert!((rbp_with_prior(&r, &q, 0.8, 0.1).unwrap() - expected).abs() < 1e-12);
+        assert_eq!(
+            rbp_with_prior(&r, &q, 0.8, 1.5),
+            Err(RbError::PriorOutOfRange(1.5))
+        );
+        assert!(rbp_with_prior(&r, &q, 0.8, f64::NAN).is_err());
+    }
+
+    #[test]
+    fn config_combines_the_variants() {
+        let r = RBRanking::from(vec![vec!["1"], vec!["2", "3"], vec!["4"], vec!["5"]]);
+        let q = graded(&[("1", 3), ("3", 1), ("4", 0)]);
+        let config = RbpConfig::default().phi(0.8).build().unwrap();
+        let score = rbp_with_config(&r, &q, &config).unwrap();
+        assert_eq!(score.bounds, rbp(&r, &q, 0.8).unwrap());
+        assert_eq!(score.expected, score.bounds.lower);
+
+        let config = RbpConfig::default()
+            .phi(0.8)
+            .depth(3)
+            .gain(GainFn::Exponential)
+            .build()
+            .unwrap();
+        assert_eq!(
+            rbp_with_config(&r, &q, &config).unwrap().bounds,
+            graded_bounds(&r, &q, 0.8, 3, &GainFn::Exponential)
+        );
+
+        let config = RbpConfig::default()
+            .phi(0.8)
+            .tie_break(TieBreak::ByDocnoDesc)
+            .prior(0.5)
+            .build()
+            .unwrap();
+        let score = rbp_with_config(&r, &q, &config).unwrap();
+        let broken = ranking(&["1", "3", "2", "4", "5"]);
+        assert_eq!(score.bounds, rbp(&broken, &q, 0.8).unwrap());
+        assert_eq!(
+            score.expected,
+            rbp_with_prior(&broken, &q, 0.8, 0.5).unwrap()
+        );
+    }
+
+    #[test]
+    fn config_rejects_invalid_options() {
+        assert!(RbpConfig::default().phi(1.0).build().is_err());
+        assert_eq!(
+            RbpConfig::default().prior(-0.1).build().unwrap_err(),
+            RbError::PriorOutOfRange(-0.1)
+        );
+    }
+
+    #[test]
+    fn depth_truncates_across_tie_groups() {
+        let r = RBRanking::from(vec![vec!["1"], vec!["2", "3"], vec!["4"]]);
+        let q = qrels(&["1", "3"], &["2", "4"]);
+        let bounds = rbp_at_depth(&r, &q, 0.8, 2).unwrap();
+        // Rank 2 carries half the relevance of the tie group {2, 3}
+        assert!((bounds.lower - (0.2 + 0.16 * 0.5)).abs() < 1e-12);
+        assert_eq!(bounds.upper, bounds.lower);
+        assert!((bounds.residual - 0.64).abs() < 1e-12);
+        assert_eq!(rbp_at_depth(&r, &q, 0.8, 10), rbp(&r, &q, 0.8));
+    }
+
+    #[test]
+    fn contributions_sum_to_rbp() {
+        let r = RBRanking::from(vec![vec!["1"], vec!["2", "3"], vec!["4"]]);
+        let q = qrels(&["1", "3", "4"], &["2"]);
+        let contributions = rbp_contributions(&r, &q, 0.8).unwrap();
+        let ranks: Vec<usize> = contributions.iter().map(|&(rank, _)| rank).collect();
+        assert_eq!(ranks, vec![1, 2, 3, 4]);
+        assert!((contributions[1].1 - 0.16 * 0.5).abs() < 1e-12);
+        let total: f64 = contributions.iter().map(|&(_, gain)| gain).sum();
+        assert!((total - rbp(&r, &q, 0.8).unwrap().lower).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rbr_normalizes_by_the_ideal_ranking() {
+        let q = qrels(&["1", "2", "5"], &[]);
+        let ideal = 1.0 - 0.8f64.powi(3);
+
+        let perfect = rbr(&ranking(&["1", "2", "5"]), &q, 0.8).unwrap();
+        assert!((perfect.score - 1.0).abs() < 1e-12);
+        assert_eq!(perfect.residual, 0.0);
+
+        // Rank 1 is relevant; 2 and 5 would be ranks 4 and 5 if appended
+        let partial = rbr(&ranking(&["1", "3", "4"]), &q, 0.8).unwrap();
+        assert!((partial.score - 0.2 / ideal).abs() < 1e-12);
+        assert!(
+            (partial.residual - (0.2 * 0.8f64.powi(3) + 0.2 * 0.8f64.powi(4)) / ideal).abs()
+                < 1e-12
+        );
+    }
+
+    #[test]
+    fn duplicate_elements_are_rejected_rather_than_credited_twice() {
+        let duplicated = ranking(&["a", "a"]);
+        let q = qrels(&["a"], &[]);
+        let duplicate = RbError::DuplicateDoc {
+            qid: None,
+            docno: "a".to_string(),
+        };
+        assert_eq!(rbr(&duplicated, &q, 0.8), Err(duplicate.clone()));
+        assert_eq!(rbp(&duplicated, &q, 0.8), Err(duplicate.clone()));
+        assert_eq!(
+            rbp_multi(&duplicated, std::slice::from_ref(&q), 0.8),
+            Err(duplicate.clone())
+        );
+        let config = RbpConfig::default().build().unwrap();
+        assert_eq!(rbp_with_config(&duplicated, &q, &config), Err(duplicate));
+        assert!((rbr(&ranking(&["a"]), &q, 0.8).unwrap().score - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn nrbp_normalizes_by_the_ideal_ranking() {
+        let q = graded(&[("1", 1), ("2", 2), ("3", 0)]);
+        let ideal = rbp(&ranking(&["2", "1"]), &q, 0.8).unwrap().lower;
+        assert_eq!(
+            nrbp(&ranking(&["2", "1", "3"]), &q, 0.8).unwrap(),
+            Some(1.0)
+        );
+        let observed = rbp(&ranking(&["3", "1", "2"]), &q, 0.8).unwrap().lower;
+        let score = nrbp(&ranking(&["3", "1", "2"]), &q, 0.8).unwrap().unwrap();
+        assert!((score - observed / ideal).abs() < 1e-12);
+    }
+
+    #[test]
+    fn nrbp_is_undefined_without_relevant_elements() {
+        let q = qrels(&[], &["1"]);
+        assert_eq!(nrbp(&ranking(&["1", "2"]), &q, 0.8), Ok(None));
+    }
+
+    #[test]
+    fn compare_is_indeterminate_within_the_residual() {
+        let bounds = |lower, residual| RbpBounds {
+            lower,
+            upper: lower,
+            residual,
+        };
+        let a = bounds(0.5, 0.1);
+        assert_eq!(rbp_compare(&a, &bounds(0.7, 0.2)), Some(Ordering::Less));
+        assert_eq!(rbp_compare(&bounds(0.7, 0.2), &a), Some(Ordering::Greater));
+        assert_eq!(rbp_compare(&a, &bounds(0.55, 0.0)), None);
+        assert_eq!(rbp_compare(&a, &a), None);
+        assert_eq!(
+            rbp_compare(&bounds(0.3, 0.0), &bounds(0.3, 0.0)),
+            Some(Ordering::Equal)
+        );
+    }
+
+    #[test]
+    fn accumulator_matches_batch_rbp() {
+        let items = ["1", "2", "3", "4", "5", "6"];
+        let q = qrels(&["1", "4", "5"], &["2", "6"]);
+        let mut accumulator = RbpAccumulator::new(0.8).unwrap();
+        for (rank, item) in items.iter().enumerate() {
+            match q.grade(item) {
+                Some(_) => accumulator.push(q.gain(item)),
+                None => accumulator.push_unjudged(),
+            }
+            let prefix = ranking(&items[..=rank]);
+            assert_eq!(accumulator.score(), rbp(&prefix, &q, 0.8).unwrap());
+        }
+        assert_eq!(accumulator.rank(), 6);
+        assert!(RbpAccumulator::new(1.0).is_err());
+    }
+
+    #[test]
+    fn set_shares_weight_across_all_positions() {
+        let docs = ["1", "2", "3", "4", "5"];
+        let r = RBRanking::from_set(docs.iter().map(|d| d.to_string()));
+        let q = qrels(&["1", "3"], &["2", "4"]);
+        let phi: f64 = 0.8;
+        let mean_relevance = 2.0 / 5.0;
+        let weight: f64 = (1..=5).map(|i| phi.powi(i - 1)).sum::<f64>() * (1.0 - phi);
+        let result = rbp(&r, &q, phi).unwrap();
+        assert!((result.lower - mean_relevance * weight).abs() < 1e-12);
+    }
+
+    #[test]
+    fn probs_give_expected_rbp() {
+        let score = rbp_from_probs(&[1.0, 0.5, 0.0], 0.8).unwrap();
+        assert!((score - 0.28).abs() < 1e-12);
+        let r = ranking(&["1", "2", "3", "4"]);
+        let binary = rbp_from_probs(&[1.0, 0.0, 1.0, 1.0], 0.8).unwrap();
+        let q = qrels(&["1", "3", "4"], &["2"]);
+        assert!((binary - rbp(&r, &q, 0.8).unwrap().lower).abs() < 1e-12);
+        assert_eq!(rbp_from_probs(&[], 0.8).unwrap(), 0.0);
+        assert_eq!(
+            rbp_from_probs(&[0.5, 1.5], 0.8),
+            Err(RbError::ProbabilityOutOfRange {
+                index: 1,
+                prob: 1.5
+            })
+        );
+    }
+
+    #[test]
+    fn signed_gain_penalizes_harmful_elements() {
+        let q = graded(&[("bad", -1), ("good", 1)]);
+        let baseline = ranking(&["x", "y"]);
+        let penalized = ranking(&["bad", "y"]);
+        let zero = rbp_graded(&baseline, &q, 0.8, &GainFn::Signed).unwrap();
+        let result = rbp_graded(&penalized, &q, 0.8, &GainFn::Signed).unwrap();
+        assert_eq!(zero.lower, 0.0);
+        assert!((result.lower - -0.2).abs() < 1e-12);
+        assert!(result.lower < zero.lower);
+        // Without the penalty the element is merely non-relevant
+        assert_eq!(rbp(&penalized, &q, 0.8).unwrap().lower, 0.0);
+        assert_eq!(nrbp(&penalized, &q, 0.8).unwrap(), Some(0.0));
+    }
+
+    #[test]
+    fn expected_depth_round_trips_through_phi() {
+        assert!((rbp_expected_depth(0.8).unwrap() - 5.0).abs() < 1e-12);
+        assert!((rbp_phi_from_expected_depth(5.0).unwrap() - 0.8).abs() < 1e-12);
+        for phi in [0.5, 0.8, 0.95, 0.99] {
+            let depth = rbp_expected_depth(phi).unwrap();
+            assert!((rbp_phi_from_expected_depth(depth).unwrap() - phi).abs() < 1e-12);
+        }
+        assert!(rbp_expected_depth(0.0).is_err());
+        assert_eq!(
+            rbp_phi_from_expected_depth(1.0),
+            Err(RbError::TargetOutOfRange(1.0))
+        );
+    }
+
+    #[test]
+    fn weight_at_depth_is_geometric_mass() {
+        let weight = rbp_weight_at_depth(0.8, 10).unwrap();
+        assert!((weight - 0.892625).abs() < 1e-6);
+        assert_eq!(rbp_weight_at_depth(0.8, 0).unwrap(), 0.0);
+        assert!(rbp_weight_at_depth(1.0, 10).is_err());
+        assert_eq!(rbp_weight_at_depth(0.8, 1 << 31).unwrap(), 1.0);
+        assert_eq!(rbp_weight_at_depth(0.8, usize::MAX).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn depth_for_weight_is_smallest_covering_depth() {
+        assert_eq!(rbp_depth_for_weight(0.8, 0.0).unwrap(), 0);
+        assert_eq!(rbp_depth_for_weight(0.8, 0.15).unwrap(), 1);
+        assert_eq!(rbp_depth_for_weight(0.8, 0.89).unwrap(), 10);
+        assert_eq!(rbp_depth_for_weight(0.8, 0.893).unwrap(), 11);
+        for target in [0.1, 0.5, 0.9, 0.99, 0.999] {
+            let k = rbp_depth_for_weight(0.95, target).unwrap();
+            assert!(rbp_weight_at_depth(0.95, k).unwrap() >= target);
+            assert!(rbp_weight_at_depth(0.95, k - 1).unwrap() < target);
+        }
+        assert_eq!(
+            rbp_depth_for_weight(0.8, 1.0),
+            Err(RbError::TargetOutOfRange(1.0))
+        );
+    }
+
+    #[test]
+    fn depth_for_weight_handles_phi_near_one() {
+        for phi in [0.999999, 1.0 - 1e-10] {
+            let k = rbp_depth_for_weight(phi, 0.5).unwrap();
+            assert!(rbp_weight_at_depth(phi, k).unwrap() >= 0.5);
+            assert!(rbp_weight_at_depth(phi, k - 1).unwrap() < 0.5);
+        }
+        // ln 2 / 1e-10 is well beyond i32::MAX
+        assert!(rbp_depth_for_weight(1.0 - 1e-10, 0.5).unwrap() > i32::MAX as usize);
+    }
+
+    #[test]
+    fn round_robin_of_identical_lists_is_the_list() {
+        let r = RBRanking::from(vec![vec!["1"], vec!["2", "3"], vec!["4"]]);
+        let q = qrels(&["1", "3"], &["4"]);
+        let rankings = [r.clone(), r.clone()];
+        for fuse in [FuseStrategy::RoundRobin, FuseStrategy::ByScore] {
+            let (bounds, fused) = rbp_fused(&rankings, &q, 0.8, fuse).unwrap();
+            assert_eq!(fused, r);
+            assert_eq!(bounds, rbp(&r, &q, 0.8).unwrap());
+        }
+    }
+
+    #[test]
+    fn fusion_keeps_the_best_position() {
+        let rankings = [ranking(&["a", "b", "c"]), ranking(&["b", "d"])];
+        let q = qrels(&["d"], &[]);
+        let (_, fused) = rbp_fused(&rankings, &q, 0.8, FuseStrategy::RoundRobin).unwrap();
+        assert_eq!(fused, ranking(&["a", "b", "d", "c"]));
+        // b scores 0.16 + 0.2, above a at 0.2, d at 0.16 and c at 0.128
+        let (_, fused) = rbp_fused(&rankings, &q, 0.8, FuseStrategy::ByScore).unwrap();
+        assert_eq!(fused, ranking(&["b", "a", "d", "c"]));
+        let (bounds, fused) = rbp_fused(&[], &q, 0.8, FuseStrategy::RoundRobin).unwrap();
+        assert!(fused.is_empty());
+        assert_eq!(bounds.lower, 0.0);
+    }
+
+    #[test]
+    fn multi_matches_rbp_for_each_assessor() {
+        let r = RBRanking::from(vec![vec!["1"], vec!["2", "3"], vec!["4"], vec!["5"]]);
+        let assessors = [
+            qrels(&["1", "4"], &["2"]),
+            graded(&[("2", 3), ("3", 1), ("5", 2)]),
+            qrels(&[], &["1", "2", "3"]),
+        ];
+        let scores = rbp_multi(&r, &assessors, 0.8).unwrap();
+        assert_eq!(scores.len(), assessors.len());
+        for (score, q) in scores.iter().zip(&assessors) {
+            assert_eq!(score.to_bits(), rbp(&r, q, 0.8).unwrap().lower.to_bits());
+        }
+        assert!(rbp_multi(&r, &[], 0.8).unwrap().is_empty());
+        assert!(rbp_multi(&r, &assessors, 0.0).is_err());
+    }
+
+    #[test]
+    fn per_query_uses_each_phi() {
+        let rankings = vec![ranking(&["1", "2"]), ranking(&["2", "1"])];
+        let sets = vec![qrels(&["1"], &[]), qrels(&["1"], &[])];
+        let scores = rbp_per_query(&rankings, &sets, &[0.5, 0.8]).unwrap();
+        assert!((scores[0].lower - 0.5).abs() < 1e-12);
+        assert!((scores[1].lower - 0.16).abs() < 1e-12);
+    }
+
+    #[test]
+    fn metrics_reject_phi_out_of_range() {
+        let r = ranking(&["1"]);
+        let q = qrels(&["1"], &[]);
+        assert_eq!(rbp(&r, &q, 1.0), Err(RbError::PhiOutOfRange(1.0)));
+        assert_eq!(rbr(&r, &q, 0.0), Err(RbError::PhiOutOfRange(0.0)));
+        assert!(rbp_contributions(&r, &q, f64::NAN).is_err());
+        assert!(rbp_per_query(&[r], &[q], &[-0.5]).is_err());
+    }
+
+    #[test]
+    fn per_query_rejects_mismatched_lengths() {
+        let rankings = vec![ranking(&["1"]), ranking(&["2"])];
+        let sets = vec![qrels(&["1"], &[]), qrels(&["2"], &[])];
+        assert_eq!(
+            rbp_per_query(&rankings, &sets, &[0.8]),
+            Err(RbError::LengthMismatch {
+                expected: 2,
+                got: 1
+            })
+        );
+    }
+
+    #[test]
+    fn batch_broadcasts_phi() {
+        let rankings = vec![ranking(&["1", "2"]), ranking(&["2", "1"])];
+        let sets = vec![qrels(&["1"], &[]), qrels(&["1"], &[])];
+        assert_eq!(
+            rbp_batch(&rankings, &sets, 0.8),
+            rbp_per_query(&rankings, &sets, &[0.8, 0.8])
+        );
+    }
+}
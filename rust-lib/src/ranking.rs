@@ -0,0 +1,374 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use crate::error::{check_len, RbError};
+
+/// How [`RBRanking::from_scores`] orders elements with equal scores.
+///
+/// The choice matters for metrics evaluated to a depth that cuts through a run
+/// of equal scores: with `Group`, the tied elements share the positions they
+/// occupy, so RBP at that depth credits the mean gain of the whole group for
+/// the positions within the depth. With any of the other policies, only the
+/// elements that the tie-break happens to place above the depth count at all.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TieBreak {
+    /// Collapse equal scores into a single tie group.
+    #[default]
+    Group,
+    /// Break ties by ascending element name.
+    ByDocnoAsc,
+    /// Break ties by descending element name, as done by `trec_eval`.
+    ByDocnoDesc,
+    /// Break ties by input order.
+    Stable,
+}
+
+/// Implements the "ranking" - a sequence of groups, where all elements within a
+/// group share the same rank. This allows ties to be handled explicitly; a
+/// ranking without ties is simply a sequence of singleton groups.
+///
+/// Elements are strings by default, as read from TREC files, but may be any
+/// type; the RBO functions accept any `T: Eq + Hash`, so that rankings of
+/// e.g. integer ids can be compared without allocating strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RBRanking<T = String> {
+    groups: Vec<Vec<T>>,
+}
+
+impl<T> RBRanking<T> {
+    /// Creates an empty ranking.
+    pub fn new() -> Self {
+        Self { groups: Vec::new() }
+    }
+
+    /// Creates a ranking from an unranked set of elements, as returned by set
+    /// retrieval: all of them form a single tie group, sharing the weight of
+    /// every position. An empty set gives an empty ranking.
+    pub fn from_set(elements: impl IntoIterator<Item = T>) -> Self {
+        let group: Vec<T> = elements.into_iter().collect();
+        let mut ranking = Self::new();
+        if !group.is_empty() {
+            ranking.append(group);
+        }
+        ranking
+    }
+
+    /// Adds a new group of tied elements to the end of the ranking.
+    pub fn append(&mut self, group: Vec<T>) {
+        self.groups.push(group);
+    }
+
+    /// Returns the number of groups (not elements) in the ranking.
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Returns true if the ranking has no groups.
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Returns the tie groups in rank order.
+    pub fn groups(&self) -> &[Vec<T>] {
+        &self.groups
+    }
+
+    /// Iterates the tie groups in rank order.
+    pub fn iter(&self) -> std::slice::Iter<'_, Vec<T>> {
+        self.groups.iter()
+    }
+
+    /// Iterates every element in rank order, flattening the tie groups.
+    pub fn elements(&self) -> impl Iterator<Item = &T> {
+        self.groups.iter().flatten()
+    }
+
+    /// Returns the total number of elements in the ranking.
+    pub fn total_elements(&self) -> usize {
+        self.groups.iter().map(Vec::len).sum()
+    }
+}
+
+impl RBRanking {
+    /// Creates a ranking from elements and their stated ranks, as in a TREC
+    /// run, where tied elements share a rank and the following rank skips the
+    /// positions they occupy: `[1, 1, 1, 4, 5]` is a tie of three followed by
+    /// two singletons.
+    ///
+    /// Returns an error if the ranks do not follow this convention, e.g.
+    /// `[1, 2, 2, 3]`, where the rank after the tie at 2 must be 4.
+    pub fn from_ranks(elements: Vec<String>, ranks: &[usize]) -> Result<Self, RbError> {
+        check_len(elements.len(), ranks.len())?;
+        let mut ranking = Self::new();
+        let mut elements = elements.into_iter();
+        let mut position = 0;
+        for group in ranks.chunk_by(|a, b| a == b) {
+            let (rank, expected) = (group[0], position + 1);
+            if rank != expected {
+                return Err(RbError::InvalidRanking(format!(
+                    "rank {rank} at position {expected} should be {expected}; ranks must \
+                     increase strictly, skipping the positions taken by tied elements"
+                )));
+            }
+            ranking.append(elements.by_ref().take(group.len()).collect());
+            position += group.len();
+        }
+        Ok(ranking)
+    }
+
+    /// Creates a ranking from `(element, score)` pairs, in descending order of
+    /// score, with elements of equal score ordered as chosen by `tie_break`.
+    /// With [`TieBreak::Group`] they form a single tie group, in input order;
+    /// with any other policy every group is a singleton.
+    ///
+    /// A NaN score carries no evidence of relevance, so its elements sink to
+    /// the bottom, below every other score, and are tied with each other as if
+    /// their scores were equal.
+    pub fn from_scores<S: Into<String>>(
+        scores: impl IntoIterator<Item = (S, f64)>,
+        tie_break: TieBreak,
+    ) -> Self {
+        let mut scores: Vec<(String, f64)> = scores
+            .into_iter()
+            .map(|(elem, score)| (elem.into(), score))
+            .collect();
+        scores.sort_by(|(a, x), (b, y)| {
+            let by_score = match (x.is_nan(), y.is_nan()) {
+                (false, false) => y.total_cmp(x),
+                (nan_x, nan_y) => nan_x.cmp(&nan_y),
+            };
+            match tie_break {
+                TieBreak::ByDocnoAsc => by_score.then_with(|| a.cmp(b)),
+                TieBreak::ByDocnoDesc => by_score.then_with(|| b.cmp(a)),
+                TieBreak::Group | TieBreak::Stable => by_score,
+            }
+        });
+
+        let mut ranking = Self::new();
+        if tie_break == TieBreak::Group {
+            for group in scores.chunk_by(|(_, x), (_, y)| x == y || (x.is_nan() && y.is_nan())) {
+                ranking.append(group.iter().map(|(elem, _)| elem.clone()).collect());
+            }
+        } else {
+            for (elem, _) in scores {
+                ranking.append(vec![elem]);
+            }
+        }
+        ranking
+    }
+
+    /// Validates the groups to ensure that:
+    ///   - No group is empty
+    ///   - We have no duplicate elements, which would be credited twice
+    pub fn validate(&self) -> Result<(), RbError> {
+        if let Some(index) = self.groups.iter().position(Vec::is_empty) {
+            return Err(RbError::InvalidRanking(format!("group {index} is empty")));
+        }
+        self.check_unique_docs()
+    }
+
+    /// Returns [`RbError::DuplicateDoc`] for the first element that appears
+    /// more than once.
+    pub(crate) fn check_unique_docs(&self) -> Result<(), RbError> {
+        match self.first_duplicate() {
+            Some((_, e)) => Err(RbError::DuplicateDoc {
+                qid: None,
+                docno: e.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the ranking with its ties broken by `tie_break`: every group
+    /// becomes a run of singletons, ordered by element name or kept in order.
+    /// With [`TieBreak::Group`] the ranking is returned unchanged.
+    pub fn break_ties(&self, tie_break: TieBreak) -> Self {
+        if tie_break == TieBreak::Group {
+            return self.clone();
+        }
+        let mut ranking = Self::new();
+        for group in &self.groups {
+            let mut group = group.clone();
+            match tie_break {
+                TieBreak::ByDocnoAsc => group.sort(),
+                TieBreak::ByDocnoDesc => group.sort_by(|a, b| b.cmp(a)),
+                TieBreak::Group | TieBreak::Stable => {}
+            }
+            group.into_iter().for_each(|e| ranking.append(vec![e]));
+        }
+        ranking
+    }
+}
+
+impl<T: Eq + Hash> RBRanking<T> {
+    /// Returns the 1-based position and value of the first element that
+    /// repeats an earlier one, if any.
+    fn first_duplicate(&self) -> Option<(usize, &T)> {
+        let mut seen = HashSet::new();
+        self.elements()
+            .enumerate()
+            .find(|&(_, e)| !seen.insert(e))
+            .map(|(index, e)| (index + 1, e))
+    }
+
+    /// Returns [`RbError::InvalidRanking`] naming the position of the first
+    /// element that appears more than once, for rankings whose elements
+    /// cannot be named in a [`RbError::DuplicateDoc`].
+    pub(crate) fn check_unique(&self) -> Result<(), RbError> {
+        match self.first_duplicate() {
+            Some((position, _)) => Err(RbError::InvalidRanking(format!(
+                "the element at position {position} appears more than once"
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T> Default for RBRanking<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Into<String>> From<Vec<Vec<S>>> for RBRanking {
+    fn from(groups: Vec<Vec<S>>) -> Self {
+        Self {
+            groups: groups
+                .into_iter()
+                .map(|group| group.into_iter().map(Into::into).collect())
+                .collect(),
+        }
+    }
+}
+
+impl<T> FromIterator<Vec<T>> for RBRanking<T> {
+    fn from_iter<I: IntoIterator<Item = Vec<T>>>(groups: I) -> Self {
+        Self {
+            groups: groups.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a RBRanking<T> {
+    type Item = &'a Vec<T>;
+    type IntoIter = std::slice::Iter<'a, Vec<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.groups.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn from_ranks_groups_ties() {
+        let ranking = RBRanking::from_ranks(strings(&["a", "b", "c", "d", "e"]), &[1, 1, 1, 4, 5]);
+        assert_eq!(
+            ranking.unwrap(),
+            RBRanking::from(vec![vec!["a", "b", "c"], vec!["d"], vec!["e"]])
+        );
+    }
+
+    #[test]
+    fn from_ranks_rejects_gapless_ties() {
+        let err = RBRanking::from_ranks(strings(&["a", "b", "c", "d"]), &[1, 2, 2, 3]).unwrap_err();
+        assert!(
+            matches!(err, RbError::InvalidRanking(msg) if msg.starts_with("rank 3 at position 4"))
+        );
+        assert!(RBRanking::from_ranks(strings(&["a", "b"]), &[2, 3]).is_err());
+        assert!(RBRanking::from_ranks(strings(&["a", "b"]), &[1]).is_err());
+    }
+
+    #[test]
+    fn validate_rejects_duplicates_and_empty_groups() {
+        assert!(RBRanking::from(vec![vec!["a"], vec!["b", "c"]])
+            .validate()
+            .is_ok());
+        assert_eq!(
+            RBRanking::from(vec![vec!["a"], vec!["b", "a"]]).validate(),
+            Err(RbError::DuplicateDoc {
+                qid: None,
+                docno: "a".to_string()
+            })
+        );
+        assert!(RBRanking::from(vec![vec!["a"], vec![]]).validate().is_err());
+    }
+
+    #[test]
+    fn from_set_is_a_single_tie_group() {
+        let ranking = RBRanking::from_set(["a", "b", "c"]);
+        assert_eq!(ranking.groups(), [vec!["a", "b", "c"]]);
+        assert!(RBRanking::from_set(Vec::<String>::new()).is_empty());
+    }
+
+    fn scores() -> Vec<(&'static str, f64)> {
+        vec![("c", 1.0), ("a", 2.0), ("d", 1.0), ("b", 1.0), ("e", 0.5)]
+    }
+
+    #[test]
+    fn group_collapses_equal_scores() {
+        assert_eq!(
+            RBRanking::from_scores(scores(), TieBreak::Group),
+            RBRanking::from(vec![vec!["a"], vec!["c", "d", "b"], vec!["e"]])
+        );
+        assert_eq!(
+            RBRanking::from_scores(scores(), TieBreak::Stable),
+            RBRanking::from(vec![vec!["a"], vec!["c"], vec!["d"], vec!["b"], vec!["e"]])
+        );
+    }
+
+    #[test]
+    fn by_docno_gives_a_strict_total_order() {
+        let ranking = RBRanking::from_scores(scores(), TieBreak::ByDocnoAsc);
+        assert_eq!(ranking.len(), ranking.total_elements());
+        let elements: Vec<&String> = ranking.elements().collect();
+        assert_eq!(elements, ["a", "b", "c", "d", "e"]);
+        let descending = RBRanking::from_scores(scores(), TieBreak::ByDocnoDesc);
+        let elements: Vec<&String> = descending.elements().collect();
+        assert_eq!(elements, ["a", "d", "c", "b", "e"]);
+    }
+
+    #[test]
+    fn nan_scores_sink_to_the_bottom() {
+        let scores = [("n", f64::NAN), ("a", 2.0), ("m", f64::NAN), ("b", -1.0)];
+        assert_eq!(
+            RBRanking::from_scores(scores, TieBreak::Group),
+            RBRanking::from(vec![vec!["a"], vec!["b"], vec!["n", "m"]])
+        );
+        let elements: Vec<String> = RBRanking::from_scores(scores, TieBreak::ByDocnoAsc)
+            .elements()
+            .cloned()
+            .collect();
+        assert_eq!(elements, ["a", "b", "m", "n"]);
+    }
+
+    #[test]
+    fn break_ties_splits_groups() {
+        let ranking = RBRanking::from(vec![vec!["c", "a", "b"], vec!["d"]]);
+        assert_eq!(ranking.break_ties(TieBreak::Group), ranking);
+        assert_eq!(
+            ranking.break_ties(TieBreak::ByDocnoAsc),
+            RBRanking::from(vec![vec!["a"], vec!["b"], vec!["c"], vec!["d"]])
+        );
+        assert_eq!(
+            ranking.break_ties(TieBreak::Stable),
+            RBRanking::from(vec![vec!["c"], vec!["a"], vec!["b"], vec!["d"]])
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip_keeps_tie_groups() {
+        let ranking = RBRanking::from(vec![vec!["a"], vec!["b", "c"], vec!["d"]]);
+        let json = serde_json::to_string(&ranking).unwrap();
+        assert_eq!(json, r#"{"groups":[["a"],["b","c"],["d"]]}"#);
+        assert_eq!(serde_json::from_str::<RBRanking>(&json).unwrap(), ranking);
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::error::{check_phi, RbError};
+use crate::ranking::RBRanking;
+
+/// Computes a rank-biased weighted Kendall's tau between rankings `a` and `b`
+/// with persistence `p`, measuring the agreement in the relative order of the
+/// elements the two rankings share.
+///
+/// Each pair of shared elements is concordant (+1) if both rankings order it
+/// the same way, discordant (-1) if they disagree, and contributes nothing if
+/// either ranking ties it. A pair whose higher element sits at rank `h_a` in
+/// `a` and `h_b` in `b` is weighted by `p^((h_a + h_b) / 2 - 1)`, the
+/// geometric mean of the persistence applied to the higher rank in each, so
+/// that inversions near the top cost more than those further down. The score
+/// is the weighted sum divided by the total weight, in [-1, 1]. As `p -> 1`
+/// every pair is weighted equally and this is Kendall's tau (tau-a), which for
+/// rankings without ties is the usual `(C - D) / (n(n-1)/2)`.
+///
+/// Ranks are positions in the full rankings, with a tie group taking the rank
+/// of its first position. Returns an error if `p` is outside (0, 1) or the
+/// rankings share fewer than two elements.
+pub fn rba<T: Eq + Hash>(a: &RBRanking<T>, b: &RBRanking<T>, p: f64) -> Result<f64, RbError> {
+    check_phi(p)?;
+    let ranks_b: HashMap<&T, usize> = ranks(b).into_iter().collect();
+    let shared: Vec<(usize, usize)> = ranks(a)
+        .into_iter()
+        .filter_map(|(e, rank_a)| ranks_b.get(e).map(|&rank_b| (rank_a, rank_b)))
+        .collect();
+    if shared.len() < 2 {
+        return Err(RbError::NotEnoughSamples {
+            needed: 2,
+            got: shared.len(),
+        });
+    }
+
+    let mut score = 0.0;
+    let mut total = 0.0;
+    for (i, &(a_i, b_i)) in shared.iter().enumerate() {
+        for &(a_j, b_j) in &shared[i + 1..] {
+            let higher = (a_i.min(a_j) + b_i.min(b_j)) as f64 / 2.0;
+            let weight = p.powf(higher - 1.0);
+            let sign = (a_i.cmp(&a_j) as i32) * (b_i.cmp(&b_j) as i32);
+            score += weight * sign as f64;
+            total += weight;
+        }
+    }
+    Ok(score / total)
+}
+
+/// Pairs each element of `ranking` with the 1-based rank of its tie group, in
+/// rank order.
+fn ranks<T>(ranking: &RBRanking<T>) -> Vec<(&T, usize)> {
+    let mut ranks = Vec::with_capacity(ranking.total_elements());
+    let mut position = 1;
+    for group in ranking {
+        ranks.extend(group.iter().map(|e| (e, position)));
+        position += group.len();
+    }
+    ranks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranking(items: &[&str]) -> RBRanking {
+        items
+            .iter()
+            .map(|item| vec![*item])
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    #[test]
+    fn identical_and_reversed_rankings() {
+        let a = ranking(&["1", "2", "3", "4"]);
+        let b = ranking(&["4", "3", "2", "1"]);
+        assert!((rba(&a, &a, 0.8).unwrap() - 1.0).abs() < 1e-12);
+        assert!((rba(&a, &b, 0.8).unwrap() + 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn weights_inversions_by_the_higher_rank() {
+        // (1, 2) is discordant with weight 1; (1, 3) and (2, 3) are concordant
+        // and each have higher ranks 1 and 2, so weight 0.5^0.5
+        let a = ranking(&["1", "2", "3"]);
+        let b = ranking(&["2", "1", "3"]);
+        let w = 0.5f64.sqrt();
+        let expected = (2.0 * w - 1.0) / (2.0 * w + 1.0);
+        assert!((rba(&a, &b, 0.5).unwrap() - expected).abs() < 1e-12);
+        assert!((rba(&a, &b, 0.5).unwrap() - rba(&b, &a, 0.5).unwrap()).abs() < 1e-12);
+
+        // An inversion at the bottom costs less than one at the top
+        let c = ranking(&["1", "3", "2"]);
+        assert!(rba(&a, &c, 0.5).unwrap() > rba(&a, &b, 0.5).unwrap());
+    }
+
+    #[test]
+    fn approaches_kendall_tau() {
+        // One discordant pair out of six
+        let a = ranking(&["1", "2", "3", "4"]);
+        let b = ranking(&["1", "2", "4", "3"]);
+        assert!((rba(&a, &b, 1.0 - 1e-9).unwrap() - 4.0 / 6.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ties_and_unshared_elements() {
+        // Only 1, 2 and 3 are shared; b ties 1 and 2, so that pair counts zero
+        let a = ranking(&["1", "2", "x", "3"]);
+        let b = RBRanking::from(vec![vec!["2", "1"], vec!["3"], vec!["y"]]);
+        let tied = rba(&a, &b, 1.0 - 1e-9).unwrap();
+        assert!((tied - 2.0 / 3.0).abs() < 1e-6);
+        assert!(rba(&a, &ranking(&["1", "z"]), 0.9).is_err());
+    }
+}
@@ -0,0 +1,1146 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::Hash;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::error::{check_len, check_phi, check_weights, RbError};
+use crate::metrics::depth_for_power;
+use crate::ranking::RBRanking;
+
+/// The bounds on the Rank-Biased Overlap between two rankings.
+///
+/// Two rankings are usually only observed to some depth `k`, so RBO cannot be
+/// computed exactly; instead it is bracketed by the minimum score (no further
+/// agreement beyond `k`) and the maximum score (as much agreement beyond `k`
+/// as the unseen tails allow).
+/// See Webber et al: <https://doi.org/10.1145/1852102.1852106>
+///
+/// The extrapolated point estimate of [`rbo_ext`] always lies within the
+/// bounds: `lower <= rbo_ext <= lower + residual`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RboResult {
+    /// RBO_min: the score assuming the unseen tails never agree (Eqn 11).
+    pub lower: f64,
+    /// The extent of unknownness due to the unseen tails; `upper - lower`.
+    pub residual: f64,
+    /// The score assuming every unseen element matches as soon as it can.
+    pub upper: f64,
+}
+
+impl RboResult {
+    /// A single point estimate of RBO, taken as the midpoint of the bounds.
+    pub fn point(&self) -> f64 {
+        self.lower + self.residual / 2.0
+    }
+}
+
+/// Computes the Rank-Biased Overlap between rankings `a` and `b` with
+/// persistence `p`.
+///
+/// At each depth `d` the agreement is the size of the overlap of the two
+/// depth-`d` prefixes divided by `d`, weighted by `(1-p)p^(d-1)`. When a tie
+/// group straddles depth `d` it contributes its expected intersection with the
+/// other prefix, so the score does not depend on the order of elements within
+/// a tie group.
+///
+/// The rankings may be of different lengths `s <= l` (Section 4.2 of Webber
+/// et al). Both are evaluated to the depth `l` of the longer one; beyond depth
+/// `s` the overlap is that of the whole shorter ranking with the prefix of the
+/// longer one. The lower bound assumes the unseen tail of the shorter ranking
+/// never agrees, and the upper bound that each of its `d - s` unseen elements
+/// at depth `d` matches an element of the longer prefix, so the residual
+/// covers exactly the overlap that is unknown from the shorter ranking.
+///
+/// Beyond depth `l` the upper bound does not assume perfect agreement, which
+/// would credit the whole tail weight `p^l`: with at most `X` elements in
+/// common at depth `l`, each further depth adds one element to each ranking,
+/// so the overlap at depth `d` is at most `X + 2(d - l)`. Agreement reaches
+/// one only from depth `2l - X`, so the more the prefixes already disagree,
+/// the tighter the bound.
+///
+/// Both rankings are always treated as prefixes of longer, unseen rankings,
+/// even when they contain the same elements, so the tail beyond `l` is left
+/// open; for complete lists whose tails are known, use [`rbo_complete`].
+///
+/// Example: for `a=[1,2,3]` and `b=[1,3,2]` with `p=0.8` the prefix score is
+/// `0.2 + 0.08 + 0.128 = 0.408`, and the tail weight `0.8^3` is bounded as
+/// above.
+///
+/// Returns an error if `p` is outside (0, 1), or either ranking is empty or
+/// has an element more than once.
+pub fn rbo<T: Eq + Hash>(a: &RBRanking<T>, b: &RBRanking<T>, p: f64) -> Result<RboResult, RbError> {
+    check_phi(p)?;
+    check_rankings(a, b)?;
+    let (_, long) = lengths(a, b);
+    Ok(bounds(a, b, p, &overlaps(a, b, long)))
+}
+
+/// Computes the bounds of [`rbo`] from the expected overlaps of `a` and `b` at
+/// every depth to the longer length.
+fn bounds<T: Eq + Hash>(a: &RBRanking<T>, b: &RBRanking<T>, p: f64, overlaps: &[f64]) -> RboResult {
+    let (short, long) = lengths(a, b);
+    let mut weight = 1.0 - p;
+    let mut lower = 0.0;
+    let mut upper = 0.0;
+    let mut max_overlap = 0.0;
+    for (d, &overlap) in overlaps.iter().enumerate() {
+        let depth = (d + 1) as f64;
+        let unseen = (d + 1).saturating_sub(short) as f64;
+        max_overlap = (overlap + unseen).min(depth);
+        lower += weight * overlap / depth;
+        upper += weight * max_overlap / depth;
+        weight *= p;
+    }
+    let overlap = overlaps[long - 1];
+    let lower = lower + tail_min(p, long, overlap);
+    let upper = upper + tail_max(p, long, max_overlap);
+    RboResult {
+        lower,
+        residual: upper - lower,
+        upper,
+    }
+}
+
+/// Computes the Rank-Biased Overlap between rankings `a` and `b` with
+/// persistence `p`, taking them to be complete lists of the same elements.
+///
+/// Unlike [`rbo`], nothing lies beyond the end of the rankings: from depth
+/// `l` on, both prefixes hold every element, so the agreement is known to be
+/// one and the whole tail weight `p^l` is credited. The residual is zero, and
+/// the score lies within the bounds of [`rbo`] for the same rankings.
+///
+/// Example: for `a=[1,2,3]` and `b=[1,3,2]` with `p=0.8` the score is
+/// `0.408 + 0.8^3 = 0.92`.
+///
+/// Returns an error if `p` is outside (0, 1), either ranking is empty or has
+/// an element more than once, or the rankings do not contain exactly the same
+/// elements.
+pub fn rbo_complete<T: Eq + Hash>(
+    a: &RBRanking<T>,
+    b: &RBRanking<T>,
+    p: f64,
+) -> Result<RboResult, RbError> {
+    check_phi(p)?;
+    check_rankings(a, b)?;
+    if !same_elements(a, b) {
+        return Err(RbError::InvalidRanking(
+            "complete rankings must contain the same elements".into(),
+        ));
+    }
+    let long = a.total_elements();
+    let mut weight = 1.0 - p;
+    let mut score = 0.0;
+    for (d, overlap) in overlaps(a, b, long).into_iter().enumerate() {
+        score += weight * overlap / (d + 1) as f64;
+        weight *= p;
+    }
+    let score = score + p.powf(long as f64);
+    Ok(RboResult {
+        lower: score,
+        residual: 0.0,
+        upper: score,
+    })
+}
+
+/// Computes the extrapolated Rank-Biased Overlap, RBO_EXT, between rankings
+/// `a` and `b` with persistence `p`.
+///
+/// Rather than bounding the unseen tails, RBO_EXT assumes that the agreement
+/// observed at the depth `s` of the shorter ranking continues unchanged to
+/// infinity (Eqn 32 of Webber et al): the unseen elements of the shorter
+/// ranking between depths `s` and `l` match at rate `X_s / s`, and beyond
+/// depth `l` the agreement is fixed at `(X_l - X_s) / l + X_s / s`. For
+/// rankings of equal length `k`, the tail weight `p^k` is credited with
+/// agreement `X_k / k`. The result lies within the bounds returned by
+/// [`rbo`], and for two orderings of the same elements equals
+/// [`rbo_complete`].
+///
+/// Returns an error if `p` is outside (0, 1), or either ranking is empty or
+/// has an element more than once.
+pub fn rbo_ext<T: Eq + Hash>(a: &RBRanking<T>, b: &RBRanking<T>, p: f64) -> Result<f64, RbError> {
+    check_phi(p)?;
+    check_rankings(a, b)?;
+    let (short, long) = lengths(a, b);
+    let overlaps = overlaps(a, b, long);
+    let (overlap_s, overlap_l) = (overlaps[short - 1], overlaps[long - 1]);
+    let rate = overlap_s / short as f64;
+
+    let mut weight = 1.0 - p;
+    let mut score = 0.0;
+    for (d, &overlap) in overlaps.iter().enumerate() {
+        let unseen = (d + 1).saturating_sub(short) as f64;
+        score += weight * (overlap + unseen * rate) / (d + 1) as f64;
+        weight *= p;
+    }
+    let tail = (overlap_l - overlap_s) / long as f64 + rate;
+    Ok(score + p.powi(long as i32) * tail)
+}
+
+/// Returns the lengths of the shorter and the longer of `a` and `b`.
+fn lengths<T>(a: &RBRanking<T>, b: &RBRanking<T>) -> (usize, usize) {
+    let (len_a, len_b) = (a.total_elements(), b.total_elements());
+    (len_a.min(len_b), len_a.max(len_b))
+}
+
+/// Computes the symmetric matrix of pairwise RBO point estimates between
+/// `rankings` with persistence `p`, where entry `[i][j]` is the midpoint of
+/// the bounds of `rbo(rankings[i], rankings[j], p)`.
+///
+/// Only the upper triangle (including the diagonal) is computed, and mirrored
+/// into the lower. With the `rayon` feature the pairs are computed in
+/// parallel. Returns an error if `p` is outside (0, 1), or any ranking is
+/// empty or has an element more than once.
+pub fn rbo_matrix<T: Eq + Hash + Sync>(
+    rankings: &[RBRanking<T>],
+    p: f64,
+) -> Result<Vec<Vec<f64>>, RbError> {
+    let n = rankings.len();
+    let pairs: Vec<(usize, usize)> = (0..n).flat_map(|i| (i..n).map(move |j| (i, j))).collect();
+    let point = |&(i, j): &(usize, usize)| rbo(&rankings[i], &rankings[j], p).map(|r| r.point());
+    #[cfg(feature = "rayon")]
+    let points: Vec<f64> = pairs.par_iter().map(point).collect::<Result<_, _>>()?;
+    #[cfg(not(feature = "rayon"))]
+    let points: Vec<f64> = pairs.iter().map(point).collect::<Result<_, _>>()?;
+
+    let mut matrix = vec![vec![0.0; n]; n];
+    for (&(i, j), score) in pairs.iter().zip(points) {
+        matrix[i][j] = score;
+        matrix[j][i] = score;
+    }
+    Ok(matrix)
+}
+
+/// Computes a bottom-weighted RBO between rankings `a` and `b`, where the
+/// persistence `p` is applied from the last rank upward, so that disagreement
+/// at the bottom of the lists dominates: e.g. to compare the tails of
+/// near-duplicate rankings.
+///
+/// Both rankings are reversed, tie groups and all, and compared by
+/// [`rbo_weighted`] with the geometric weights `(1-p)p^(d-1)` renormalized
+/// over their length. This only makes sense for complete lists of the same
+/// length, as there is no unseen tail to bound: both lists end at the same,
+/// known bottom. Two identical lists without ties score one.
+///
+/// Returns an error if `p` is outside (0, 1), either ranking is empty or has
+/// an element more than once, or the rankings have different numbers of
+/// elements.
+pub fn rbo_reversed<T: Eq + Hash + Clone>(
+    a: &RBRanking<T>,
+    b: &RBRanking<T>,
+    p: f64,
+) -> Result<f64, RbError> {
+    check_phi(p)?;
+    check_len(a.total_elements(), b.total_elements())?;
+    let reversed =
+        |ranking: &RBRanking<T>| -> RBRanking<T> { ranking.iter().rev().cloned().collect() };
+    let weights: Vec<f64> = std::iter::successors(Some(1.0 - p), |w| Some(w * p))
+        .take(a.total_elements())
+        .collect();
+    rbo_weighted(&reversed(a), &reversed(b), &weights)
+}
+
+/// Finds the ranking of `corpus` closest to `query` by RBO with persistence
+/// `p`, e.g. to detect a near-duplicate, returning its index and the midpoint
+/// of its bounds from [`rbo`]. Of equally close rankings the first is chosen.
+///
+/// Rather than computing the RBO of every candidate in full, each candidate is
+/// first walked depth by depth while bounding its score from above. At depth
+/// `d` the agreement so far is known (up to what [`rbo`] assumes of the unseen
+/// elements of the shorter ranking), and the agreement at every deeper depth
+/// is at most one, so
+///
+/// ```text
+/// rbo <= upper <= sum_{i <= d} (1-p) p^(i-1) max(A_i) + p^d
+/// ```
+///
+/// where `max(A_i)` is the largest agreement at depth `i` consistent with the
+/// prefixes. The right-hand side only falls as `d` grows; once it is no more
+/// than the best score so far, the candidate cannot beat it and the walk
+/// stops. Finding a close match early thus cuts short the walk of every
+/// dissimilar candidate, which typically diverges within a few ranks.
+///
+/// Returns an error if `p` is outside (0, 1), `corpus` is empty, or any
+/// ranking is empty or has an element more than once.
+pub fn rbo_best_match<T: Eq + Hash>(
+    query: &RBRanking<T>,
+    corpus: &[RBRanking<T>],
+    p: f64,
+) -> Result<(usize, f64), RbError> {
+    best_match(query, corpus, p).map(|(best, _)| best)
+}
+
+/// Computes [`rbo_best_match`], along with the number of candidates whose
+/// walk was not cut short, and so had their bounds computed in full.
+fn best_match<T: Eq + Hash>(
+    query: &RBRanking<T>,
+    corpus: &[RBRanking<T>],
+    p: f64,
+) -> Result<((usize, f64), usize), RbError> {
+    check_phi(p)?;
+    if corpus.is_empty() {
+        return Err(RbError::NotEnoughSamples { needed: 1, got: 0 });
+    }
+    check_ranking(query)?;
+    let index = GroupIndex::new(query);
+    let mut best: Option<(usize, f64)> = None;
+    let mut evaluated = 0;
+    for (i, candidate) in corpus.iter().enumerate() {
+        check_ranking(candidate)?;
+        let (short, long) = lengths(candidate, query);
+        let mut overlaps = Vec::with_capacity(long);
+        let mut weight = 1.0 - p;
+        let mut upper = 0.0;
+        let complete = overlaps_while(candidate, &index, long, |overlap| {
+            let d = overlaps.len() + 1;
+            let unseen = d.saturating_sub(short) as f64;
+            upper += weight * (overlap + unseen).min(d as f64) / d as f64;
+            weight *= p;
+            overlaps.push(overlap);
+            best.is_none_or(|(_, score)| upper + p.powi(d as i32) > score)
+        });
+        if !complete {
+            continue;
+        }
+        evaluated += 1;
+        let score = bounds(candidate, query, p, &overlaps).point();
+        if best.is_none_or(|(_, best)| score > best) {
+            best = Some((i, score));
+        }
+    }
+    Ok((best.expect("the corpus is not empty"), evaluated))
+}
+
+/// Returns the smallest depth `k` to which two rankings must be compared for
+/// the residual of [`rbo`] to be at most `tol`, whatever the rankings.
+///
+/// For rankings of length `k`, the lower and upper bounds share the prefix
+/// sum over depths `1..=k`, which leaves the residual within the weight of the
+/// tail beyond `k`:
+///
+/// ```text
+/// residual <= sum_{d > k} (1 - p) p^(d-1) = p^k
+/// ```
+///
+/// so `p^k <= tol` suffices, which holds for `k >= ln(tol) / ln(p)`. The
+/// bound holds before seeing any element, so the actual residual reported by
+/// [`rbo`] at that depth is usually smaller still. A `tol` of at least one
+/// needs no depth at all.
+///
+/// Returns an error if `p` is outside (0, 1) or `tol` is not positive.
+pub fn rbo_depth_for_residual(p: f64, tol: f64) -> Result<usize, RbError> {
+    check_phi(p)?;
+    depth_for_power(p, tol)
+}
+
+/// Computes the overlap between rankings `a` and `b` under an arbitrary
+/// vector of depth weights, where `weights[d]` is applied to the agreement at
+/// depth `d+1`.
+///
+/// The rankings are evaluated to the shortest of the two rankings and the
+/// weight vector, and the weights are renormalized to sum to one over that
+/// prefix; there is no tail, so no residual. The standard [`rbo`] is the
+/// special case of geometric weights `(1-p)p^(d-1)`, which over a prefix of
+/// depth `k` sum to `1-p^k`: with those weights this is the prefix part of
+/// RBO divided by `1-p^k`. If every weight in the prefix is zero the overlap
+/// is zero. Returns an error if any weight is negative, or either ranking is
+/// empty or has an element more than once.
+pub fn rbo_weighted<T: Eq + Hash>(
+    a: &RBRanking<T>,
+    b: &RBRanking<T>,
+    weights: &[f64],
+) -> Result<f64, RbError> {
+    check_rankings(a, b)?;
+    check_weights(weights)?;
+
+    let depth = a
+        .total_elements()
+        .min(b.total_elements())
+        .min(weights.len());
+
+    let mut score = 0.0;
+    let mut total = 0.0;
+    for (d, (&overlap, &weight)) in overlaps(a, b, depth).iter().zip(weights).enumerate() {
+        score += weight * overlap / (d + 1) as f64;
+        total += weight;
+    }
+    Ok(if total > 0.0 { score / total } else { 0.0 })
+}
+
+/// Checks the properties that [`rbo`] and [`rbo_ext`] must satisfy for the
+/// rankings `a` and `b`, as a guard against regressions:
+///   - Symmetry: `rbo(a, b, p)` equals `rbo(b, a, p)`
+///   - Ordering: `0 <= lower <= rbo_ext <= upper <= 1`
+///   - Self-overlap: the upper bound of `rbo(a, a, p)` and the score of
+///     `rbo_complete(a, a, p)` are one, for each of `a` and `b` without ties
+///
+/// A ranking with ties does not have a self-overlap of one, as its two copies
+/// may break the ties differently; see [`rbo`]. Comparisons allow for a small
+/// floating point error. Returns [`RbError::InvariantViolated`] describing the
+/// first property that does not hold, or any error of [`rbo`].
+pub fn check_invariants<T: Eq + Hash>(
+    a: &RBRanking<T>,
+    b: &RBRanking<T>,
+    p: f64,
+) -> Result<(), RbError> {
+    const EPSILON: f64 = 1e-9;
+    let violated = |msg: String| Err(RbError::InvariantViolated(msg));
+
+    let forward = rbo(a, b, p)?;
+    let backward = rbo(b, a, p)?;
+    if (forward.lower - backward.lower).abs() > EPSILON
+        || (forward.upper - backward.upper).abs() > EPSILON
+    {
+        return violated(format!("asymmetric: {forward:?} and {backward:?}"));
+    }
+
+    let ext = rbo_ext(a, b, p)?;
+    let ordered = -EPSILON <= forward.lower
+        && forward.lower <= ext + EPSILON
+        && ext <= forward.upper + EPSILON
+        && forward.upper <= 1.0 + EPSILON;
+    if !ordered {
+        return violated(format!("unordered: {forward:?} with ext {ext}"));
+    }
+
+    for ranking in [a, b] {
+        if ranking.len() != ranking.total_elements() {
+            continue;
+        }
+        let own = rbo(ranking, ranking, p)?;
+        let complete = rbo_complete(ranking, ranking, p)?;
+        if (own.upper - 1.0).abs() > EPSILON || (complete.lower - 1.0).abs() > EPSILON {
+            return violated(format!(
+                "self-overlap is {own:?}, or {complete:?} when complete"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `ranking` is non-empty and has no element more than once, as
+/// a repeated element would be counted towards the overlap at every depth.
+fn check_ranking<T: Eq + Hash>(ranking: &RBRanking<T>) -> Result<(), RbError> {
+    if ranking.total_elements() == 0 {
+        return Err(RbError::EmptyRanking);
+    }
+    ranking.check_unique()
+}
+
+fn check_rankings<T: Eq + Hash>(a: &RBRanking<T>, b: &RBRanking<T>) -> Result<(), RbError> {
+    check_ranking(a)?;
+    check_ranking(b)
+}
+
+/// Returns true if `a` and `b` are rankings of exactly the same elements,
+/// given that neither has an element more than once (see [`check_rankings`]).
+fn same_elements<T: Eq + Hash>(a: &RBRanking<T>, b: &RBRanking<T>) -> bool {
+    if a.total_elements() != b.total_elements() {
+        return false;
+    }
+    let elements: HashSet<&T> = a.elements().collect();
+    b.elements().all(|e| elements.contains(e))
+}
+
+/// Returns the expected overlap between the depth-`d` prefixes of `a` and `b`,
+/// for each depth `d` from one to `depth`. Past the end of a ranking, its
+/// prefix is the whole ranking.
+///
+/// When a tie group of size `g` starting after position `s` straddles depth
+/// `d`, we do not know which of its elements fall within the prefix. Breaking
+/// the tie uniformly at random, each of its elements is within the prefix with
+/// probability `(d - s) / g`. Breaking the ties of `a` and `b` independently,
+/// the expected overlap is the sum over all elements `e` of
+/// `P(e in a[..d]) * P(e in b[..d])`. For rankings without ties this is the
+/// usual overlap count.
+///
+/// As every element of a group shares the same probability, the sum is taken
+/// over pairs of groups, weighted by the size of their intersection; this keeps
+/// the result independent of the order of elements within a group.
+fn overlaps<T: Eq + Hash>(a: &RBRanking<T>, b: &RBRanking<T>, depth: usize) -> Vec<f64> {
+    let mut overlaps = Vec::with_capacity(depth);
+    overlaps_while(a, &GroupIndex::new(b), depth, |overlap| {
+        overlaps.push(overlap);
+        true
+    });
+    overlaps
+}
+
+/// The non-empty tie groups of a ranking, with the group of each element.
+struct GroupIndex<'a, T> {
+    groups: Vec<&'a Vec<T>>,
+    group_of: HashMap<&'a T, usize>,
+}
+
+impl<'a, T: Eq + Hash> GroupIndex<'a, T> {
+    fn new(ranking: &'a RBRanking<T>) -> Self {
+        let groups: Vec<&Vec<T>> = ranking.iter().filter(|g| !g.is_empty()).collect();
+        let group_of = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(j, group)| group.iter().map(move |e| (e, j)))
+            .collect();
+        Self { groups, group_of }
+    }
+}
+
+/// Walks the expected overlaps of [`overlaps`] between `a` and the ranking
+/// indexed by `index`, passing each to `visit` until it returns false or
+/// `depth` is reached. Returns true if the walk reached `depth`.
+///
+/// Only the groups of `a` within the walk are ever intersected with those of
+/// the other ranking, so stopping early saves the work beyond that depth.
+fn overlaps_while<T: Eq + Hash>(
+    a: &RBRanking<T>,
+    index: &GroupIndex<'_, T>,
+    depth: usize,
+    mut visit: impl FnMut(f64) -> bool,
+) -> bool {
+    let groups_a: Vec<&Vec<T>> = a.iter().filter(|g| !g.is_empty()).collect();
+    let groups_b = &index.groups;
+
+    // The size of the intersection of each pair of groups, filled in as the
+    // groups of a are reached; a group not yet reached has none of its
+    // elements in the prefix, so contributes nothing
+    let mut pairs = Intersections {
+        rows: vec![Vec::new(); groups_a.len()],
+        cols: vec![Vec::new(); groups_b.len()],
+    };
+
+    // The fraction of each group within the current prefix
+    let mut frac_a = vec![0.0; groups_a.len()];
+    let mut frac_b = vec![0.0; groups_b.len()];
+
+    let (mut i, mut taken_a) = (0, 0);
+    let (mut j, mut taken_b) = (0, 0);
+    let mut overlap = 0.0;
+    for _ in 0..depth {
+        let group_a = (i < groups_a.len()).then_some(i);
+        let group_b = (j < groups_b.len()).then_some(j);
+        if let Some(i) = group_a.filter(|_| taken_a == 0) {
+            pairs.add(i, groups_a[i], index);
+        }
+        let before = pairs.active(group_a, group_b, &frac_a, &frac_b);
+        if let Some(i) = group_a {
+            taken_a += 1;
+            frac_a[i] = taken_a as f64 / groups_a[i].len() as f64;
+        }
+        if let Some(j) = group_b {
+            taken_b += 1;
+            frac_b[j] = taken_b as f64 / groups_b[j].len() as f64;
+        }
+        overlap += pairs.active(group_a, group_b, &frac_a, &frac_b) - before;
+        if !visit(overlap) {
+            return false;
+        }
+
+        // Move on to the next groups once these are exhausted
+        if group_a.is_some_and(|i| taken_a == groups_a[i].len()) {
+            i += 1;
+            taken_a = 0;
+        }
+        if group_b.is_some_and(|j| taken_b == groups_b[j].len()) {
+            j += 1;
+            taken_b = 0;
+        }
+    }
+    true
+}
+
+/// The sizes of the intersections between the groups of two rankings, by
+/// group of the first (rows) and of the second (columns).
+struct Intersections {
+    rows: Vec<Vec<(usize, f64)>>,
+    cols: Vec<Vec<(usize, f64)>>,
+}
+
+impl Intersections {
+    /// Intersects group `i` of the first ranking with every group of the
+    /// second, indexed by `index`.
+    fn add<T: Eq + Hash>(&mut self, i: usize, group: &[T], index: &GroupIndex<'_, T>) {
+        let mut counts: BTreeMap<usize, usize> = BTreeMap::new();
+        for e in group {
+            if let Some(&j) = index.group_of.get(e) {
+                *counts.entry(j).or_default() += 1;
+            }
+        }
+        for (j, count) in counts {
+            self.rows[i].push((j, count as f64));
+            self.cols[j].push((i, count as f64));
+        }
+    }
+
+    /// Returns the contribution of the pairs that involve group `i` of the
+    /// first ranking or group `j` of the second, either of which is absent
+    /// once its ranking is exhausted.
+    fn active(&self, i: Option<usize>, j: Option<usize>, frac_a: &[f64], frac_b: &[f64]) -> f64 {
+        let row: f64 = i.map_or(0.0, |i| {
+            self.rows[i]
+                .iter()
+                .map(|&(jj, c)| c * frac_a[i] * frac_b[jj])
+                .sum()
+        });
+        let col: f64 = j.map_or(0.0, |j| {
+            self.cols[j]
+                .iter()
+                .filter(|&&(ii, _)| Some(ii) != i)
+                .map(|&(ii, c)| c * frac_a[ii] * frac_b[j])
+                .sum()
+        });
+        row + col
+    }
+}
+
+/// Computes the tail sum from `depth + 1` to infinity with the overlap fixed at
+/// `overlap`. See: Eqn 11 of Webber et al.
+fn tail_min(p: f64, depth: usize, overlap: f64) -> f64 {
+    let mut partial = 0.0;
+    let mut weight = 1.0;
+    for d in 1..=depth {
+        weight *= p;
+        partial += weight / d as f64;
+    }
+    (1.0 - p) / p * overlap * ((1.0 / (1.0 - p)).ln() - partial)
+}
+
+/// Computes the tail sum from `depth + 1` to infinity with the overlap
+/// growing from at most `max_overlap` at `depth` by two per depth, until it
+/// reaches full agreement, as in Section 4 of Webber et al.
+fn tail_max(p: f64, depth: usize, max_overlap: f64) -> f64 {
+    let mut weight = (1.0 - p) * p.powi(depth as i32);
+    let mut tail = 0.0;
+    let mut d = depth + 1;
+    loop {
+        let overlap = max_overlap + 2.0 * (d - depth) as f64;
+        if overlap >= d as f64 {
+            // Every deeper depth agrees fully, leaving weight p^(d-1)
+            return tail + weight / (1.0 - p);
+        }
+        tail += weight * overlap / d as f64;
+        weight *= p;
+        d += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    fn ranking(items: &[&str]) -> RBRanking {
+        items
+            .iter()
+            .map(|item| vec![*item])
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    #[test]
+    fn identical_rankings_leave_the_tail_open() {
+        let a = ranking(&["a", "b", "c", "d"]);
+        let p: f64 = 0.9;
+        let result = rbo(&a, &a, p).unwrap();
+        assert!((result.lower - (1.0 - p.powi(4) + tail_min(p, 4, 4.0))).abs() < 1e-12);
+        assert!((result.upper - 1.0).abs() < 1e-12);
+        assert!(result.residual > 0.0);
+    }
+
+    #[test]
+    fn permutation_matches_worked_example() {
+        let a = ranking(&["1", "2", "3"]);
+        let b = ranking(&["1", "3", "2"]);
+        let result = rbo(&a, &b, 0.8).unwrap();
+        assert!((result.lower - (0.408 + tail_min(0.8, 3, 3.0))).abs() < 1e-12);
+        assert!(result.residual > 0.0);
+        // Extending one ranking only changes the bounds by what the new
+        // element adds, rather than opening a tail that was closed
+        let extended = ranking(&["1", "3", "2", "4"]);
+        let longer = rbo(&a, &extended, 0.8).unwrap();
+        assert!(
+            (longer.lower - (0.408 + 0.8f64.powi(3) * 0.2 * 0.75 + tail_min(0.8, 4, 3.0))).abs()
+                < 1e-12
+        );
+        assert!(longer.residual > 0.0);
+    }
+
+    #[test]
+    fn complete_permutation_matches_worked_example() {
+        let a = ranking(&["1", "2", "3"]);
+        let b = ranking(&["1", "3", "2"]);
+        let result = rbo_complete(&a, &b, 0.8).unwrap();
+        assert!((result.lower - (0.408 + 0.512)).abs() < 1e-12);
+        assert_eq!(result.residual, 0.0);
+        let bounds = rbo(&a, &b, 0.8).unwrap();
+        assert!(bounds.lower <= result.lower && result.upper <= bounds.upper);
+    }
+
+    #[test]
+    fn complete_rankings_must_share_elements() {
+        let a = ranking(&["1", "2", "3"]);
+        let b = ranking(&["1", "3", "2", "4"]);
+        assert!(matches!(
+            rbo_complete(&a, &b, 0.8),
+            Err(RbError::InvalidRanking(_))
+        ));
+        let c = ranking(&["1", "3", "4"]);
+        assert!(matches!(
+            rbo_complete(&a, &c, 0.8),
+            Err(RbError::InvalidRanking(_))
+        ));
+        // A repeated element in either ranking is not a permutation
+        let (x, repeated) = (ranking(&["x", "y"]), ranking(&["x", "x"]));
+        for (a, b) in [(&x, &repeated), (&repeated, &x)] {
+            assert!(matches!(
+                rbo_complete(a, b, 0.8),
+                Err(RbError::InvalidRanking(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn duplicate_elements_are_rejected() {
+        let repeated = ranking(&["x", "x"]);
+        let b = ranking(&["x", "y"]);
+        let invalid =
+            |result: Result<f64, RbError>| matches!(result, Err(RbError::InvalidRanking(_)));
+        assert!(invalid(rbo(&repeated, &b, 0.9).map(|r| r.upper)));
+        assert!(invalid(rbo(&b, &repeated, 0.9).map(|r| r.upper)));
+        assert!(invalid(rbo_ext(&repeated, &b, 0.9)));
+        assert!(invalid(rbo_weighted(&b, &repeated, &[0.5, 0.5])));
+        assert!(invalid(
+            rbo_matrix(&[b.clone(), repeated.clone()], 0.9).map(|m| m[0][1])
+        ));
+        // Rather than ranking the duplicate above the real match
+        let corpus = [repeated.clone(), b.clone()];
+        assert!(invalid(rbo_best_match(&b, &corpus, 0.9).map(|(_, s)| s)));
+        assert!(invalid(
+            rbo_best_match(&repeated, &[b], 0.9).map(|(_, s)| s)
+        ));
+    }
+
+    #[test]
+    fn disjoint_prefixes_leave_the_tail_open() {
+        let a = ranking(&["a", "b", "c"]);
+        let b = ranking(&["x", "y", "z"]);
+        let result = rbo(&a, &b, 0.8).unwrap();
+        assert_eq!(result.lower, 0.0);
+        // At most 2 of 4, 4 of 5, then all of 6 may agree
+        let upper = 0.2 * (0.8f64.powi(3) * 0.5 + 0.8f64.powi(4) * 0.8) + 0.8f64.powi(5);
+        assert!((result.upper - upper).abs() < 1e-12);
+        assert!(result.upper < 0.512);
+    }
+
+    #[test]
+    fn identical_prefixes_score_one_only_when_complete() {
+        let items = ["a", "b", "c", "d", "e"];
+        for depth in 1..=items.len() {
+            let a = ranking(&items[..depth]);
+            for p in [0.5, 0.9, 0.99] {
+                let result = rbo(&a, &a, p).unwrap();
+                assert!(result.residual > 0.0);
+                assert!((result.upper - 1.0).abs() < 1e-12);
+                let complete = rbo_complete(&a, &a, p).unwrap();
+                assert_eq!(complete.residual, 0.0);
+                assert!((complete.lower - 1.0).abs() < 1e-12);
+            }
+        }
+        // Swapped ties leave the same elements, so only the prefix is unknown
+        // when the lists are complete
+        let a = RBRanking::from(vec![vec!["a"], vec!["b", "c"], vec!["d"]]);
+        let b = RBRanking::from(vec![vec!["a"], vec!["c", "b"], vec!["d"]]);
+        assert!(rbo(&a, &b, 0.9).unwrap().residual > 0.0);
+        assert_eq!(rbo_complete(&a, &b, 0.9).unwrap().residual, 0.0);
+    }
+
+    #[test]
+    fn permuted_prefixes_reach_their_complete_score_at_most() {
+        // Holding the same elements, the prefixes can agree fully from depth
+        // l on, so the tight upper bound is the score as complete lists
+        for (a, b) in [
+            (["1", "2", "3"], ["1", "3", "2"]),
+            (["a", "b", "c"], ["c", "b", "a"]),
+        ] {
+            let (a, b) = (ranking(&a), ranking(&b));
+            for p in [0.5, 0.8, 0.95] {
+                let upper = rbo(&a, &b, p).unwrap().upper;
+                assert!((upper - rbo_complete(&a, &b, p).unwrap().upper).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn tight_upper_bound_never_exceeds_loose_bound() {
+        let a = ranking(&["a", "b", "c", "d", "e", "f"]);
+        let b = ranking(&["b", "x", "a", "y", "f", "z"]);
+        let p: f64 = 0.9;
+        let result = rbo(&a, &b, p).unwrap();
+        let prefix: f64 = [0.0, 1.0, 2.0, 2.0, 2.0, 3.0]
+            .iter()
+            .enumerate()
+            .map(|(d, x)| (1.0 - p) * p.powi(d as i32) * x / (d + 1) as f64)
+            .sum();
+        assert!(result.upper < prefix + p.powi(6));
+        assert!(result.lower < result.upper);
+    }
+
+    #[test]
+    fn swapping_tied_elements_leaves_rbo_unchanged() {
+        let a = RBRanking::from(vec![vec!["a"], vec!["b", "c"], vec!["d"], vec!["e"]]);
+        let b = RBRanking::from(vec![vec!["c"], vec!["a"], vec!["x"], vec!["b"], vec!["d"]]);
+        let swapped = RBRanking::from(vec![vec!["a"], vec!["c", "b"], vec!["d"], vec!["e"]]);
+        assert_eq!(rbo(&a, &b, 0.9).unwrap(), rbo(&swapped, &b, 0.9).unwrap());
+        assert_eq!(rbo(&b, &a, 0.9).unwrap(), rbo(&b, &swapped, 0.9).unwrap());
+    }
+
+    #[test]
+    fn tied_groups_contribute_expected_overlap() {
+        // At depth 1 each of b and c is in the prefix of a with probability
+        // 1/2, and c is in the prefix of b, so the expected overlap is 1/2
+        let a = RBRanking::from(vec![vec!["b", "c"], vec!["d"]]);
+        let b = ranking(&["c", "d", "e"]);
+        let p: f64 = 0.8;
+        let prefix = (1.0 - p) * (0.5 + p * 1.0 / 2.0 + p * p * 2.0 / 3.0);
+        let result = rbo(&a, &b, p).unwrap();
+        assert!((result.upper - (prefix + p.powi(3))).abs() < 1e-12);
+    }
+
+    #[test]
+    fn weighted_overlap_renormalizes_weights() {
+        let a = ranking(&["1", "2", "3"]);
+        let b = ranking(&["1", "3", "2"]);
+        let uniform = rbo_weighted(&a, &b, &[2.0, 2.0, 2.0, 2.0]).unwrap();
+        assert!((uniform - (1.0 + 0.5 + 1.0) / 3.0).abs() < 1e-12);
+        assert!((rbo_weighted(&a, &a, &[0.5, 0.1, 3.0]).unwrap() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn geometric_weights_recover_rbo_prefix() {
+        let a = ranking(&["a", "b", "c", "d"]);
+        let b = ranking(&["b", "x", "a", "c"]);
+        let p: f64 = 0.9;
+        let weights: Vec<f64> = (0..4).map(|d| (1.0 - p) * p.powi(d)).collect();
+        let result = rbo(&a, &b, p).unwrap();
+        let prefix = result.upper - p.powi(4);
+        let weighted = rbo_weighted(&a, &b, &weights).unwrap();
+        assert!((weighted - prefix / (1.0 - p.powi(4))).abs() < 1e-12);
+    }
+
+    #[test]
+    fn weighted_overlap_rejects_negative_weights() {
+        let a = ranking(&["a", "b"]);
+        assert_eq!(
+            rbo_weighted(&a, &a, &[1.0, -0.5]),
+            Err(RbError::InvalidWeight {
+                index: 1,
+                weight: -0.5
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_empty_rankings_and_bad_persistence() {
+        let a = ranking(&["a"]);
+        assert_eq!(rbo(&a, &RBRanking::new(), 0.9), Err(RbError::EmptyRanking));
+        assert_eq!(rbo(&a, &a, 1.5), Err(RbError::PhiOutOfRange(1.5)));
+    }
+
+    #[test]
+    fn extrapolated_matches_worked_example() {
+        // The overlaps of abcdefg and zcavwxy are 0, 0, 2, 2, 2, 2, 2
+        let a = ranking(&["a", "b", "c", "d", "e", "f", "g"]);
+        let b = ranking(&["z", "c", "a", "v", "w", "x", "y"]);
+        let p: f64 = 0.9;
+        let prefix: f64 = (3..=7)
+            .map(|d| (1.0 - p) * p.powi(d - 1) * 2.0 / d as f64)
+            .sum();
+        let ext = rbo_ext(&a, &b, p).unwrap();
+        assert!((ext - (prefix + p.powi(7) * 2.0 / 7.0)).abs() < 1e-12);
+        assert!((ext - 0.288217285714).abs() < 1e-9);
+
+        let result = rbo(&a, &b, p).unwrap();
+        assert!(result.lower <= ext && ext <= result.lower + result.residual);
+    }
+
+    #[test]
+    fn extrapolated_equals_complete_score_of_permutation() {
+        let a = ranking(&["1", "2", "3"]);
+        let b = ranking(&["1", "3", "2"]);
+        let ext = rbo_ext(&a, &b, 0.8).unwrap();
+        assert!((ext - rbo_complete(&a, &b, 0.8).unwrap().lower).abs() < 1e-12);
+    }
+
+    #[test]
+    fn uneven_lengths_evaluate_to_the_longer_depth() {
+        // The shorter ranking is a prefix of the longer, so the overlap is
+        // known to be d up to depth 5 and at least 5 beyond it; the unseen
+        // tail of the shorter ranking could still agree perfectly
+        let items: Vec<String> = (0..50).map(|d| d.to_string()).collect();
+        let long = ranking(&items.iter().map(String::as_str).collect::<Vec<_>>());
+        let short = ranking(&items[..5].iter().map(String::as_str).collect::<Vec<_>>());
+        let p: f64 = 0.9;
+        let prefix: f64 = (1..=50)
+            .map(|d| (1.0 - p) * p.powi(d - 1) * (d.min(5) as f64) / d as f64)
+            .sum();
+        let result = rbo(&short, &long, p).unwrap();
+        assert!((result.lower - (prefix + tail_min(p, 50, 5.0))).abs() < 1e-12);
+        assert!((result.upper - 1.0).abs() < 1e-12);
+        assert_eq!(result, rbo(&long, &short, p).unwrap());
+    }
+
+    #[test]
+    fn uneven_residual_covers_unseen_tail_of_shorter() {
+        let short = ranking(&["a", "b", "c", "d", "e"]);
+        let names: Vec<String> = (0..50).map(|d| format!("x{d}")).collect();
+        let long = ranking(&names.iter().map(String::as_str).collect::<Vec<_>>());
+        let p: f64 = 0.9;
+        // Each unseen element of the shorter ranking may match in the longer
+        let unknown: f64 = (6..=50)
+            .map(|d| (1.0 - p) * p.powi(d - 1) * (d - 5) as f64 / d as f64)
+            .sum();
+        // Beyond depth 50 at most 45 + 2(d - 50) may agree, until depth 55
+        let tail: f64 = (51..=54)
+            .map(|d| (1.0 - p) * p.powi(d - 1) * (45 + 2 * (d - 50)) as f64 / d as f64)
+            .sum();
+        let result = rbo(&short, &long, p).unwrap();
+        assert_eq!(result.lower, 0.0);
+        assert!((result.residual - (unknown + tail + p.powi(54))).abs() < 1e-12);
+
+        let ext = rbo_ext(&short, &long, p).unwrap();
+        assert_eq!(ext, 0.0);
+    }
+
+    #[test]
+    fn uneven_extrapolation_lies_within_bounds() {
+        let short = ranking(&["a", "b", "c", "d", "e"]);
+        let long = ranking(&["b", "x", "a", "y", "c", "z", "e", "w", "q", "r"]);
+        let p = 0.8;
+        let result = rbo(&short, &long, p).unwrap();
+        let ext = rbo_ext(&short, &long, p).unwrap();
+        assert!(result.lower < ext && ext < result.upper);
+        assert_eq!(ext, rbo_ext(&long, &short, p).unwrap());
+    }
+
+    #[test]
+    fn matrix_is_symmetric_and_matches_pairwise() {
+        let rankings = vec![
+            ranking(&["a", "b", "c", "d"]),
+            ranking(&["b", "a", "x", "d"]),
+            ranking(&["x", "y", "z"]),
+        ];
+        let matrix = rbo_matrix(&rankings, 0.9).unwrap();
+        assert_eq!(matrix.len(), 3);
+        for i in 0..3 {
+            for j in 0..3 {
+                assert_eq!(matrix[i][j], matrix[j][i]);
+                let pairwise = rbo(&rankings[i], &rankings[j], 0.9).unwrap().point();
+                assert!((matrix[i][j] - pairwise).abs() < 1e-12);
+            }
+        }
+        assert!(matrix[0][0] > matrix[0][1] && matrix[0][0] > matrix[0][2]);
+        assert_eq!(rbo_matrix(&rankings, 0.0), Err(RbError::PhiOutOfRange(0.0)));
+    }
+
+    #[test]
+    fn integer_rankings_match_string_rankings() {
+        let ids: RBRanking<u64> = [vec![1], vec![2, 3], vec![4]].into_iter().collect();
+        let other: RBRanking<u64> = [vec![3], vec![1], vec![5], vec![2]].into_iter().collect();
+        let a = RBRanking::from(vec![vec!["1"], vec!["2", "3"], vec!["4"]]);
+        let b = RBRanking::from(vec![vec!["3"], vec!["1"], vec!["5"], vec!["2"]]);
+        assert_eq!(rbo(&ids, &other, 0.9), rbo(&a, &b, 0.9));
+        assert_eq!(rbo_ext(&ids, &other, 0.9), rbo_ext(&a, &b, 0.9));
+    }
+
+    /// Generates rankings of up to 20 elements drawn from the same 30, so
+    /// that pairs overlap, split into tie groups of up to three.
+    fn tied_ranking() -> impl Strategy<Value = RBRanking<u32>> {
+        (
+            Just((0..30).collect::<Vec<u32>>()).prop_shuffle(),
+            1..=20usize,
+        )
+            .prop_flat_map(|(elements, n)| (Just(elements[..n].to_vec()), vec(1..=3usize, n)))
+            .prop_map(|(mut elements, sizes)| {
+                let mut ranking = RBRanking::new();
+                for size in sizes {
+                    if elements.is_empty() {
+                        break;
+                    }
+                    let rest = elements.split_off(size.min(elements.len()));
+                    ranking.append(std::mem::replace(&mut elements, rest));
+                }
+                ranking
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn invariants_hold_for_tied_rankings(
+            a in tied_ranking(),
+            b in tied_ranking(),
+            p in 0.01..0.99f64,
+        ) {
+            prop_assert_eq!(check_invariants(&a, &b, p), Ok(()));
+        }
+
+        #[test]
+        fn untied_rankings_overlap_themselves_fully(a in tied_ranking(), p in 0.01..0.99f64) {
+            let untied: RBRanking<u32> = a.elements().map(|&e| vec![e]).collect();
+            let result = rbo(&untied, &untied, p).unwrap();
+            prop_assert!((result.upper - 1.0).abs() < 1e-9);
+            let complete = rbo_complete(&untied, &untied, p).unwrap();
+            prop_assert!((complete.lower - 1.0).abs() < 1e-9);
+            prop_assert_eq!(complete.residual, 0.0);
+        }
+    }
+
+    #[test]
+    fn check_invariants_accepts_worked_examples() {
+        let a = ranking(&["a", "b", "c", "d", "e", "f", "g"]);
+        let b = ranking(&["z", "c", "a", "v", "w", "x", "y"]);
+        assert_eq!(check_invariants(&a, &b, 0.9), Ok(()));
+        let a = RBRanking::from(vec![vec!["a"], vec!["b", "c"], vec!["d"]]);
+        assert_eq!(check_invariants(&a, &a, 0.9), Ok(()));
+    }
+
+    #[test]
+    fn depth_for_residual_bounds_any_residual() {
+        assert_eq!(rbo_depth_for_residual(0.9, 0.01).unwrap(), 44);
+        assert_eq!(rbo_depth_for_residual(0.98, 0.01).unwrap(), 228);
+        assert_eq!(rbo_depth_for_residual(0.5, 0.25).unwrap(), 2);
+        assert_eq!(rbo_depth_for_residual(0.9, 1.0).unwrap(), 0);
+        assert_eq!(
+            rbo_depth_for_residual(0.9, 0.0),
+            Err(RbError::TargetOutOfRange(0.0))
+        );
+
+        let k = rbo_depth_for_residual(0.9, 0.01).unwrap();
+        let items: Vec<String> = (0..2 * k).map(|i| i.to_string()).collect();
+        let refs: Vec<&str> = items.iter().map(String::as_str).collect();
+        let (a, b) = (ranking(&refs[..k]), ranking(&refs[k..]));
+        assert!(rbo(&a, &b, 0.9).unwrap().residual <= 0.01);
+        let tied = RBRanking::from(vec![refs[..k].to_vec()]);
+        assert!(rbo(&tied, &a, 0.9).unwrap().residual <= 0.01);
+    }
+
+    #[test]
+    fn depth_for_residual_handles_p_near_one() {
+        for p in [0.999999, 1.0 - 1e-10] {
+            let k = rbo_depth_for_residual(p, 0.5).unwrap();
+            assert!(p.powf(k as f64) <= 0.5);
+            assert!(p.powf((k - 1) as f64) > 0.5);
+        }
+        assert!(rbo_depth_for_residual(1.0 - 1e-10, 0.5).unwrap() > i32::MAX as usize);
+    }
+
+    #[test]
+    fn reversed_weights_the_bottom_of_the_lists() {
+        let a = ranking(&["1", "2", "3", "4", "5", "6", "7", "8"]);
+        let b = ranking(&["1", "2", "3", "4", "x", "y", "z", "w"]);
+        let top = rbo_weighted(&a, &b, &[0.2, 0.16, 0.128, 0.1024, 0.08192]).unwrap();
+        let bottom = rbo_reversed(&a, &b, 0.8).unwrap();
+        assert!(top > 0.9);
+        assert!(bottom < 0.1);
+        assert!(rbo(&a, &b, 0.8).unwrap().lower > bottom);
+        assert!((rbo_reversed(&a, &a, 0.8).unwrap() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn reversed_requires_equal_lengths() {
+        let a = ranking(&["1", "2", "3"]);
+        let b = ranking(&["1", "2"]);
+        assert_eq!(
+            rbo_reversed(&a, &b, 0.8),
+            Err(RbError::LengthMismatch {
+                expected: 3,
+                got: 2
+            })
+        );
+        assert!(rbo_reversed(&a, &a, 1.0).is_err());
+    }
+
+    #[test]
+    fn best_match_agrees_with_brute_force() {
+        let corpus: Vec<RBRanking<u32>> = (0..40u32)
+            .map(|c| {
+                let mut ranking = RBRanking::new();
+                for d in 0..30u32 {
+                    let e = (d * (c % 7 + 1) + c / 7) % 50;
+                    if !ranking.elements().any(|&x| x == e) {
+                        ranking.append(vec![e]);
+                    }
+                }
+                ranking
+            })
+            .collect();
+        for q in [0, 3, 17, 39] {
+            let query = &corpus[q];
+            let brute = corpus
+                .iter()
+                .enumerate()
+                .filter(|&(c, _)| c != q)
+                .map(|(c, r)| (c, rbo(r, query, 0.9).unwrap().point()))
+                .fold(None, |best: Option<(usize, f64)>, (c, s)| match best {
+                    Some((_, b)) if b >= s => best,
+                    _ => Some((c, s)),
+                })
+                .unwrap();
+            let others: Vec<RBRanking<u32>> = corpus
+                .iter()
+                .enumerate()
+                .filter(|&(c, _)| c != q)
+                .map(|(_, r)| r.clone())
+                .collect();
+            let (index, score) = rbo_best_match(query, &others, 0.9).unwrap();
+            let index = if index >= q { index + 1 } else { index };
+            assert_eq!((index, score), brute);
+        }
+    }
+
+    #[test]
+    fn best_match_finds_the_duplicate() {
+        let query = ranking(&["a", "b", "c", "d"]);
+        let corpus = [
+            ranking(&["x", "y", "z"]),
+            ranking(&["a", "c", "b", "e"]),
+            ranking(&["d", "c", "b", "a"]),
+            query.clone(),
+            ranking(&["a", "b", "c", "d"]),
+        ];
+        let (index, score) = rbo_best_match(&query, &corpus, 0.9).unwrap();
+        assert_eq!(index, 3);
+        assert_eq!(score, rbo(&query, &query, 0.9).unwrap().point());
+        assert!(rbo_best_match(&query, &[], 0.9).is_err());
+    }
+
+    #[test]
+    fn best_match_prunes_dissimilar_candidates() {
+        let items: Vec<String> = (0..60).map(|i| i.to_string()).collect();
+        let refs: Vec<&str> = items.iter().map(String::as_str).collect();
+        let query = ranking(&refs[..10]);
+        let mut corpus = vec![query.clone()];
+        corpus.extend(refs[10..].chunks(10).map(ranking));
+        // Once the duplicate is found, every disjoint candidate is cut short at
+        // the first depth, where at most p^1 of agreement remains
+        let ((index, _), evaluated) = best_match(&query, &corpus, 0.5).unwrap();
+        assert_eq!(index, 0);
+        assert_eq!(evaluated, 1);
+        // Found last, each disjoint candidate before it could still match the
+        // equal score of the one before, so none is pruned
+        corpus.rotate_left(1);
+        let ((index, _), evaluated) = best_match(&query, &corpus, 0.5).unwrap();
+        assert_eq!(index, corpus.len() - 1);
+        assert_eq!(evaluated, corpus.len());
+    }
+
+    #[test]
+    fn bounds_are_ordered() {
+        let a = ranking(&["a", "b", "c", "d", "e"]);
+        let b = ranking(&["b", "x", "a", "y", "z"]);
+        let result = rbo(&a, &b, 0.9).unwrap();
+        assert!(result.lower > 0.0);
+        assert!(result.lower <= result.point() && result.point() <= result.upper);
+        assert!((result.upper - result.lower - result.residual).abs() < 1e-12);
+    }
+}
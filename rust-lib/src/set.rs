@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use crate::error::RbError;
+
+/// Judgments at or above this relevance are treated as positive.
+pub const POSITIVE_CUTOFF: i32 = 1;
+
+/// How [`RBSet::merge`] resolves an element judged with different grades by
+/// the two sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Keep the higher grade.
+    Max,
+    /// Keep the lower grade.
+    Min,
+    /// Keep the grade of the set merged in.
+    Last,
+    /// Fail with [`RbError::ConflictingJudgment`].
+    Error,
+}
+
+/// Implements the "set" - stores the relevance grade of each judged element.
+/// Elements with a grade of at least [`POSITIVE_CUTOFF`] are positive, judged
+/// elements below it are negative, and elements not in the set are unjudged.
+/// Negative grades are kept as given, so they may be credited as penalties by
+/// [`crate::GainFn::Signed`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RBSet {
+    grades: HashMap<String, i32>,
+}
+
+impl RBSet {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a binary set where `positive` elements have grade 1 and
+    /// `negative` elements have grade 0.
+    pub fn from_binary<P, N>(positive: P, negative: N) -> Self
+    where
+        P: IntoIterator,
+        P::Item: Into<String>,
+        N: IntoIterator,
+        N::Item: Into<String>,
+    {
+        let mut set = Self::new();
+        negative.into_iter().for_each(|elem| set.add_negative(elem));
+        positive.into_iter().for_each(|elem| set.add_positive(elem));
+        set
+    }
+
+    /// Creates a graded set from a map of element to relevance grade.
+    pub fn from_graded(grades: HashMap<String, i32>) -> Self {
+        Self { grades }
+    }
+
+    /// Adds an element with relevance grade `rel`.
+    pub fn add(&mut self, elem: impl Into<String>, rel: i32) {
+        self.grades.insert(elem.into(), rel);
+    }
+
+    /// Adds an element as positive, with grade 1.
+    pub fn add_positive(&mut self, elem: impl Into<String>) {
+        self.add(elem, 1);
+    }
+
+    /// Adds an element as negative, with grade 0.
+    pub fn add_negative(&mut self, elem: impl Into<String>) {
+        self.add(elem, 0);
+    }
+
+    /// Returns the grade of `elem`, or `None` if it is unjudged.
+    pub fn grade(&self, elem: &str) -> Option<i32> {
+        self.grades.get(elem).copied()
+    }
+
+    /// Returns the gain of `elem` for RBP: its grade, with negative and
+    /// unjudged elements contributing nothing.
+    pub fn gain(&self, elem: &str) -> f64 {
+        self.grade(elem).map_or(0.0, |grade| grade.max(0) as f64)
+    }
+
+    /// Returns true if `elem` is judged positive.
+    pub fn is_positive(&self, elem: &str) -> bool {
+        self.grade(elem)
+            .is_some_and(|grade| grade >= POSITIVE_CUTOFF)
+    }
+
+    /// Returns true if `elem` is judged negative.
+    pub fn is_negative(&self, elem: &str) -> bool {
+        self.grade(elem)
+            .is_some_and(|grade| grade < POSITIVE_CUTOFF)
+    }
+
+    /// Iterates the positive elements.
+    pub fn positive(&self) -> impl Iterator<Item = &String> {
+        self.grades
+            .iter()
+            .filter(|(_, &grade)| grade >= POSITIVE_CUTOFF)
+            .map(|(elem, _)| elem)
+    }
+
+    /// Iterates every judged element with its grade.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, i32)> {
+        self.grades
+            .iter()
+            .map(|(elem, &grade)| (elem.as_str(), grade))
+    }
+
+    /// Returns the total number of elements in the set.
+    pub fn total_elements(&self) -> usize {
+        self.grades.len()
+    }
+
+    /// Returns the union of this set and `other`, e.g. the judgments of two
+    /// pools, with each element judged by both sets given one grade chosen by
+    /// `policy`. Elements judged the same by both are kept as they are.
+    ///
+    /// Under [`MergePolicy::Error`], returns an error naming the first
+    /// conflicting element in name order, along with its grade in this set then
+    /// in `other`.
+    pub fn merge(&self, other: &RBSet, policy: MergePolicy) -> Result<RBSet, RbError> {
+        if policy == MergePolicy::Error {
+            let conflict = other
+                .iter()
+                .filter_map(|(elem, second)| match self.grade(elem) {
+                    Some(first) if first != second => Some((elem, first, second)),
+                    _ => None,
+                })
+                .min();
+            if let Some((docno, first, second)) = conflict {
+                return Err(RbError::ConflictingJudgment {
+                    docno: docno.to_string(),
+                    first,
+                    second,
+                });
+            }
+        }
+        let mut merged = self.clone();
+        for (elem, grade) in other.iter() {
+            let grade = match (merged.grade(elem), policy) {
+                (Some(first), MergePolicy::Max) => first.max(grade),
+                (Some(first), MergePolicy::Min) => first.min(grade),
+                _ => grade,
+            };
+            merged.add(elem, grade);
+        }
+        Ok(merged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graded(grades: &[(&str, i32)]) -> RBSet {
+        RBSet::from_graded(grades.iter().map(|&(e, g)| (e.to_string(), g)).collect())
+    }
+
+    fn pools() -> (RBSet, RBSet) {
+        (
+            graded(&[("a", 2), ("b", 0), ("c", 1)]),
+            graded(&[("b", 1), ("c", 1), ("d", 3), ("a", 1)]),
+        )
+    }
+
+    #[test]
+    fn merge_resolves_conflicts_by_policy() {
+        let (first, second) = pools();
+        let merged = first.merge(&second, MergePolicy::Max).unwrap();
+        assert_eq!(merged, graded(&[("a", 2), ("b", 1), ("c", 1), ("d", 3)]));
+        let merged = first.merge(&second, MergePolicy::Min).unwrap();
+        assert_eq!(merged, graded(&[("a", 1), ("b", 0), ("c", 1), ("d", 3)]));
+        let merged = first.merge(&second, MergePolicy::Last).unwrap();
+        assert_eq!(merged, graded(&[("a", 1), ("b", 1), ("c", 1), ("d", 3)]));
+    }
+
+    #[test]
+    fn merge_error_reports_both_grades() {
+        let (first, second) = pools();
+        let err = first.merge(&second, MergePolicy::Error).unwrap_err();
+        assert_eq!(
+            err,
+            RbError::ConflictingJudgment {
+                docno: "a".to_string(),
+                first: 2,
+                second: 1
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "conflicting judgments of a: grades 2 and 1"
+        );
+        let agreeing = graded(&[("c", 1), ("e", 0)]);
+        let merged = first.merge(&agreeing, MergePolicy::Error).unwrap();
+        assert_eq!(merged.total_elements(), 4);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::error::{check_len, RbError};
+
+/// Computes a two-sided p-value for the difference between paired per-query
+/// scores `a` and `b`, such as the RBP of two systems over the same queries,
+/// using a paired bootstrap over the per-query differences.
+///
+/// The differences are shifted to have a mean of zero, as under the null
+/// hypothesis, and resampled with replacement `iters` times. The p-value is
+/// the fraction of resamples whose mean difference is at least as far from
+/// zero as the observed mean difference. The resampling is driven by a
+/// generator seeded with `seed`, so the same seed gives the same p-value.
+///
+/// Returns an error if `a` and `b` differ in length, or if either the scores
+/// or `iters` are empty.
+pub fn paired_bootstrap(a: &[f64], b: &[f64], iters: usize, seed: u64) -> Result<f64, RbError> {
+    check_len(a.len(), b.len())?;
+    check_samples(1, a.len())?;
+    check_samples(1, iters)?;
+
+    let n = a.len();
+    let diffs: Vec<f64> = a.iter().zip(b).map(|(x, y)| x - y).collect();
+    let observed = diffs.iter().sum::<f64>() / n as f64;
+    let shifted: Vec<f64> = diffs.iter().map(|d| d - observed).collect();
+
+    let mut rng = SplitMix64(seed);
+    let extreme = (0..iters)
+        .filter(|_| {
+            let total: f64 = (0..n).map(|_| shifted[rng.below(n)]).sum();
+            (total / n as f64).abs() >= observed.abs()
+        })
+        .count();
+    Ok(extreme as f64 / iters as f64)
+}
+
+/// Computes the jackknife estimate of the standard error of the mean of the
+/// per-query scores `per_query`, e.g. for error bars on a mean RBP.
+///
+/// Each query is left out in turn and the mean of the rest recomputed. With
+/// `n` queries and leave-one-out means `m_i` averaging to `m`, the standard
+/// error is `sqrt((n - 1) / n * sum((m_i - m)^2))`, which for the mean equals
+/// the sample standard deviation over `sqrt(n)`.
+///
+/// Returns an error if there are fewer than two queries.
+pub fn jackknife_se(per_query: &[f64]) -> Result<f64, RbError> {
+    check_samples(2, per_query.len())?;
+    let n = per_query.len() as f64;
+    let total: f64 = per_query.iter().sum();
+    let means: Vec<f64> = per_query.iter().map(|x| (total - x) / (n - 1.0)).collect();
+    let mean = means.iter().sum::<f64>() / n;
+    let spread: f64 = means.iter().map(|m| (m - mean).powi(2)).sum();
+    Ok(((n - 1.0) / n * spread).sqrt())
+}
+
+fn check_samples(needed: usize, got: usize) -> Result<(), RbError> {
+    if got >= needed {
+        Ok(())
+    } else {
+        Err(RbError::NotEnoughSamples { needed, got })
+    }
+}
+
+/// A small, fast generator for resampling; see Steele et al, "Fast
+/// splittable pseudorandom number generators".
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value uniformly distributed in `[0, n)`.
+    fn below(&mut self, n: usize) -> usize {
+        ((self.next() as u128 * n as u128) >> 64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bootstrap_is_reproducible_with_a_seed() {
+        let a = [0.3, 0.5, 0.2, 0.8, 0.4, 0.6];
+        let b = [0.2, 0.5, 0.3, 0.6, 0.1, 0.6];
+        let first = paired_bootstrap(&a, &b, 1000, 42).unwrap();
+        assert_eq!(first, paired_bootstrap(&a, &b, 1000, 42).unwrap());
+        assert!((0.0..=1.0).contains(&first));
+    }
+
+    #[test]
+    fn bootstrap_detects_consistent_differences() {
+        let a: Vec<f64> = (0..30).map(|q| 0.5 + (q % 7) as f64 / 100.0).collect();
+        let b: Vec<f64> = a.iter().map(|s| s - 0.1).collect();
+        assert!(paired_bootstrap(&a, &b, 1000, 7).unwrap() < 0.01);
+        assert_eq!(paired_bootstrap(&a, &a, 1000, 7).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn bootstrap_rejects_bad_input() {
+        assert_eq!(
+            paired_bootstrap(&[0.1, 0.2], &[0.1], 100, 0),
+            Err(RbError::LengthMismatch {
+                expected: 2,
+                got: 1
+            })
+        );
+        assert!(paired_bootstrap(&[], &[], 100, 0).is_err());
+        assert!(paired_bootstrap(&[0.1], &[0.2], 0, 0).is_err());
+    }
+
+    #[test]
+    fn jackknife_matches_standard_error_of_the_mean() {
+        let scores = [0.3, 0.5, 0.2, 0.8, 0.4, 0.6];
+        let n = scores.len() as f64;
+        let mean = scores.iter().sum::<f64>() / n;
+        let variance = scores.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        let se = jackknife_se(&scores).unwrap();
+        assert!((se - (variance / n).sqrt()).abs() < 1e-12);
+        assert_eq!(jackknife_se(&[0.4, 0.4, 0.4]).unwrap(), 0.0);
+        assert_eq!(
+            jackknife_se(&[0.4]),
+            Err(RbError::NotEnoughSamples { needed: 2, got: 1 })
+        );
+    }
+}